@@ -0,0 +1,64 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use borsh::BorshDeserialize;
+
+use crate::models::InnerInstruction;
+use crate::trade_parser::anchor_discriminator;
+
+/// Prefix of every self-CPI event instruction (`emit_cpi!`). It's followed by
+/// the event discriminator (first 8 bytes of sha256("event:<Name>")) and the
+/// Borsh-encoded event.
+pub const EVENT_IX_TAG: u64 = u64::from_le_bytes([228, 69, 165, 46, 81, 203, 154, 29]);
+
+/// Prefix of the log line an `emit!` event is written to, followed by the
+/// base64 of the event discriminator and the Borsh-encoded event.
+const EVENT_LOG_PREFIX: &str = "Program data: ";
+
+/// Decodes a self-CPI event from instruction data, `None` if the data is not
+/// an event or carries a different one. Trailing fields not declared on `T`
+/// are ignored, so event structs only need the leading fields they read.
+pub fn parse_event_cpi<T: BorshDeserialize>(data: &[u8], discriminator: u64) -> Option<T> {
+    if anchor_discriminator(data)? != EVENT_IX_TAG
+        || anchor_discriminator(data.get(8..)?)? != discriminator
+    {
+        return None;
+    }
+    T::deserialize(&mut data.get(16..)?).ok()
+}
+
+/// All events with the given discriminator that `program_id` emitted among
+/// `instructions`, in emission order.
+pub fn find_event_cpis<T: BorshDeserialize>(
+    instructions: &[InnerInstruction],
+    accounts: &[String],
+    program_id: &str,
+    discriminator: u64,
+) -> Vec<T> {
+    instructions
+        .iter()
+        .filter(|inst| {
+            accounts
+                .get(inst.program_id_index as usize)
+                .is_some_and(|program| program == program_id)
+        })
+        .filter_map(|inst| parse_event_cpi(&inst.data, discriminator))
+        .collect()
+}
+
+/// Decodes an `emit!` event from a log line, `None` if the line is not an
+/// event or carries a different one.
+pub fn parse_event_log<T: BorshDeserialize>(line: &str, discriminator: u64) -> Option<T> {
+    let encoded = line.strip_prefix(EVENT_LOG_PREFIX)?;
+    let bytes = STANDARD.decode(encoded.trim()).ok()?;
+    if anchor_discriminator(&bytes)? != discriminator {
+        return None;
+    }
+    T::deserialize(&mut bytes.get(8..)?).ok()
+}
+
+/// All `emit!` events with the given discriminator among a program's log
+/// lines, in emission order.
+pub fn find_event_logs<T: BorshDeserialize>(logs: &[String], discriminator: u64) -> Vec<T> {
+    logs.iter()
+        .filter_map(|line| parse_event_log(line, discriminator))
+        .collect()
+}
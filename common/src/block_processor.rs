@@ -4,28 +4,54 @@ use std::{
 };
 
 use crate::{
-    global::OUTPUT_PATH,
-    models::{TokenBalance, TradeData, UiTokenAmount, ZmqData},
-    tx_processor::process_tx,
+    global::CAPTURE_FAILED_SWAPS,
+    models::{
+        FailedSwap, LiquidityEvent, Migration, PoolCreated, RouteTrade, SkippedCounts, TradeData,
+    },
+    routes::synthesize_route,
+    sandwich::detect_sandwiches,
+    tx_processor::{process_failed_tx, process_tx},
     utils::{
-        convert_to_date, get_amt, get_mint, get_signer_balance_change, save_trades_to_avro,
-        save_trades_to_csv,
+        save_skipped_to_json, save_to_avro, AVRO_SCHEMA, FAILED_AVRO_SCHEMA, LIQUIDITY_AVRO_SCHEMA,
+        MIGRATION_AVRO_SCHEMA, POOL_AVRO_SCHEMA, ROUTE_AVRO_SCHEMA, SANDWICH_AVRO_SCHEMA,
     },
 };
 use chrono::{DateTime, Utc};
 use solana_transaction_status::{EncodedConfirmedBlock, UiInnerInstructions};
 use std::time::Duration;
 
+/// Points migrations that don't name their pool at the first pool created
+/// for their mint, in the same transaction or a later one of the block: a
+/// curve withdrawn to the migration wallet is followed by the pool seeded with
+/// its reserves. Migrations whose pool shows up in a later block stay
+/// unlinked.
+fn link_migrations(migrations: &mut [Migration], pools_created: &[PoolCreated]) {
+    for migration in migrations.iter_mut() {
+        if !migration.destination_pool.is_empty() {
+            continue;
+        }
+        if let Some(pool) = pools_created
+            .iter()
+            .find(|pool| pool.base_mint == migration.mint || pool.quote_mint == migration.mint)
+        {
+            migration.destination_program = pool.program.clone();
+            migration.destination_pool = pool.pool_address.clone();
+        }
+    }
+}
+
 pub async fn process_block(
     slot: u64, // node returns wrong slot
     block: EncodedConfirmedBlock,
     publisher_clone: Option<Arc<Mutex<zmq::Socket>>>,
 ) -> Result<()> {
-    let timestamp = block.block_time.expect("Block time not found");
+    let timestamp = block
+        .block_time
+        .ok_or_else(|| anyhow::anyhow!("Block time not found"))?;
     let mut data: Vec<TradeData> = vec![];
 
     // convert timestamp to human readable timestamp
-    let d = UNIX_EPOCH + Duration::from_secs(timestamp.try_into().unwrap());
+    let d = UNIX_EPOCH + Duration::from_secs(timestamp.try_into()?);
     // Create DateTime from SystemTime
     let datetime = DateTime::<Utc>::from(d);
     // Formats the combined date and time with the specified format string.
@@ -33,12 +59,40 @@ pub async fn process_block(
 
     let date_str = datetime.format("%Y-%m-%d").to_string();
 
+    let mut routes: Vec<RouteTrade> = vec![];
+    let mut failed_swaps: Vec<FailedSwap> = vec![];
+    let mut liquidity_events: Vec<LiquidityEvent> = vec![];
+    let mut pools_created: Vec<PoolCreated> = vec![];
+    let mut migrations: Vec<Migration> = vec![];
+    let mut skipped = SkippedCounts {
+        block_slot: slot,
+        ..Default::default()
+    };
+
     for trx in block.transactions {
-        match process_tx(trx, slot, timestamp).await {
-            Some(trades) => {
-                data.extend(trades);
+        if *CAPTURE_FAILED_SWAPS && trx.meta.as_ref().is_some_and(|meta| meta.err.is_some()) {
+            match process_failed_tx(trx, slot, timestamp).await {
+                Ok(swaps) => failed_swaps.extend(swaps),
+                Err(err) => skipped.record(&err),
+            }
+            continue;
+        }
+        match process_tx(trx, slot, timestamp, &mut skipped).await {
+            Ok(decoded) => {
+                // an aggregator's own account of the route beats piecing it
+                // together from the legs
+                if !decoded.routes.is_empty() {
+                    routes.extend(decoded.routes);
+                } else if let Some(route) = synthesize_route(&decoded.trades) {
+                    routes.push(route);
+                }
+                data.extend(decoded.trades);
+                liquidity_events.extend(decoded.liquidity_events);
+                migrations.extend(decoded.migrations);
+                link_migrations(&mut migrations, &decoded.pools_created);
+                pools_created.extend(decoded.pools_created);
             }
-            None => {}
+            Err(err) => skipped.record(&err),
         }
     }
 
@@ -46,8 +100,34 @@ pub async fn process_block(
     let current_datetime = DateTime::<Utc>::from(current_time);
     let current_timestamp_str = current_datetime.format("%Y-%m-%d %H:%M:%S.%f").to_string();
 
-    save_trades_to_avro(&data, &date_str, slot)
-        .await?;
+    if !failed_swaps.is_empty() {
+        save_to_avro(&failed_swaps, &FAILED_AVRO_SCHEMA, "failed", &date_str, slot).await?;
+    }
+    if !liquidity_events.is_empty() {
+        save_to_avro(&liquidity_events, &LIQUIDITY_AVRO_SCHEMA, "liquidity", &date_str, slot)
+            .await?;
+    }
+    if !pools_created.is_empty() {
+        save_to_avro(&pools_created, &POOL_AVRO_SCHEMA, "pools", &date_str, slot).await?;
+    }
+    if !migrations.is_empty() {
+        save_to_avro(&migrations, &MIGRATION_AVRO_SCHEMA, "migrations", &date_str, slot).await?;
+    }
+    let sandwiches = detect_sandwiches(&data);
+    if !sandwiches.is_empty() {
+        save_to_avro(&sandwiches, &SANDWICH_AVRO_SCHEMA, "sandwiches", &date_str, slot).await?;
+    }
+    if !routes.is_empty() {
+        save_to_avro(&routes, &ROUTE_AVRO_SCHEMA, "routes", &date_str, slot).await?;
+    }
+    if !skipped.is_empty() {
+        save_skipped_to_json(&skipped, &date_str, slot).await?;
+    }
+
+    if data.is_empty() {
+        return Err(anyhow::anyhow!("No trades to save"));
+    }
+    save_to_avro(&data, &AVRO_SCHEMA, "", &date_str, slot).await?;
 
     // TODO: ZMQ
     // let zmq_data: ZmqData = ZmqData {
@@ -0,0 +1,142 @@
+use solana_sdk::bs58;
+use solana_transaction_status::UiCompiledInstruction;
+
+pub const COMPUTE_BUDGET_PROGRAM_ID: &str = "ComputeBudget111111111111111111111111111111";
+
+const SET_COMPUTE_UNIT_LIMIT: u8 = 2;
+const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+
+/// Compute units each instruction gets when the transaction sets no limit,
+/// and the most a transaction can request.
+const DEFAULT_UNITS_PER_INSTRUCTION: u32 = 200_000;
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+/// Compute budget a transaction requested through the ComputeBudget program.
+pub struct ComputeBudget {
+    pub cu_limit: u32,
+    pub cu_price_micro_lamports: u64,
+}
+
+impl ComputeBudget {
+    /// What the transaction paid on top of the signature fee: the unit price
+    /// times the requested limit, rounded up.
+    pub fn priority_fee_lamports(&self) -> u64 {
+        let micro_lamports = self.cu_price_micro_lamports as u128 * self.cu_limit as u128;
+        micro_lamports.div_ceil(MICRO_LAMPORTS_PER_LAMPORT) as u64
+    }
+}
+
+/// Reads the `SetComputeUnitLimit` and `SetComputeUnitPrice` instructions
+/// among a transaction's top-level instructions. Without an explicit limit
+/// every other instruction gets the default allowance, capped at the
+/// transaction maximum; without a price there's no priority fee.
+pub fn parse_compute_budget(
+    instructions: &[UiCompiledInstruction],
+    all_addresses: &[String],
+) -> ComputeBudget {
+    let mut cu_limit = None;
+    let mut cu_price_micro_lamports = 0;
+    let mut other_instructions: u32 = 0;
+
+    for inst in instructions {
+        let is_compute_budget = all_addresses
+            .get(inst.program_id_index as usize)
+            .is_some_and(|program_id| program_id == COMPUTE_BUDGET_PROGRAM_ID);
+        if !is_compute_budget {
+            other_instructions += 1;
+            continue;
+        }
+        let data = match bs58::decode(&inst.data).into_vec() {
+            Ok(data) => data,
+            Err(_) => continue,
+        };
+        match data.split_first() {
+            Some((&SET_COMPUTE_UNIT_LIMIT, args)) => {
+                if let Some(bytes) = args.get(..4).and_then(|bytes| bytes.try_into().ok()) {
+                    cu_limit = Some(u32::from_le_bytes(bytes));
+                }
+            }
+            Some((&SET_COMPUTE_UNIT_PRICE, args)) => {
+                if let Some(bytes) = args.get(..8).and_then(|bytes| bytes.try_into().ok()) {
+                    cu_price_micro_lamports = u64::from_le_bytes(bytes);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    ComputeBudget {
+        cu_limit: cu_limit
+            .unwrap_or(other_instructions.saturating_mul(DEFAULT_UNITS_PER_INSTRUCTION))
+            .min(MAX_COMPUTE_UNIT_LIMIT),
+        cu_price_micro_lamports,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accounts() -> Vec<String> {
+        vec![
+            String::from("payer"),
+            String::from(COMPUTE_BUDGET_PROGRAM_ID),
+            String::from("dex"),
+        ]
+    }
+
+    fn instruction(program_id_index: u8, data: &[u8]) -> UiCompiledInstruction {
+        UiCompiledInstruction {
+            program_id_index,
+            accounts: vec![],
+            data: bs58::encode(data).into_string(),
+            stack_height: None,
+        }
+    }
+
+    fn set_limit(units: u32) -> UiCompiledInstruction {
+        let mut data = vec![SET_COMPUTE_UNIT_LIMIT];
+        data.extend(units.to_le_bytes());
+        instruction(1, &data)
+    }
+
+    fn set_price(micro_lamports: u64) -> UiCompiledInstruction {
+        let mut data = vec![SET_COMPUTE_UNIT_PRICE];
+        data.extend(micro_lamports.to_le_bytes());
+        instruction(1, &data)
+    }
+
+    #[test]
+    fn explicit_limit_and_price() {
+        let instructions = [set_limit(300_000), set_price(25_000), instruction(2, &[9])];
+        let budget = parse_compute_budget(&instructions, &accounts());
+        assert_eq!(budget.cu_limit, 300_000);
+        assert_eq!(budget.cu_price_micro_lamports, 25_000);
+        // 300k units at 0.025 lamports each
+        assert_eq!(budget.priority_fee_lamports(), 7_500);
+    }
+
+    #[test]
+    fn priority_fee_rounds_up() {
+        let instructions = [set_limit(3), set_price(1)];
+        assert_eq!(
+            parse_compute_budget(&instructions, &accounts()).priority_fee_lamports(),
+            1
+        );
+    }
+
+    #[test]
+    fn default_limit_per_other_instruction_capped() {
+        let instructions = [set_price(10), instruction(2, &[9]), instruction(2, &[9])];
+        assert_eq!(
+            parse_compute_budget(&instructions, &accounts()).cu_limit,
+            400_000
+        );
+
+        let instructions = vec![instruction(2, &[9]); 10];
+        let budget = parse_compute_budget(&instructions, &accounts());
+        assert_eq!(budget.cu_limit, MAX_COMPUTE_UNIT_LIMIT);
+        assert_eq!(budget.priority_fee_lamports(), 0);
+    }
+}
@@ -0,0 +1,38 @@
+use thiserror::Error;
+
+/// Why a transaction, or one of its swap instructions, couldn't be decoded.
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    #[error("transaction has no status meta")]
+    MissingMeta,
+    #[error("transaction could not be decoded from its encoding")]
+    UndecodableTransaction,
+    #[error("transaction has no token balances")]
+    MissingTokenBalances,
+    #[error("transaction has no signer")]
+    MissingSigner,
+    #[error("instruction {index} data is not valid base58")]
+    InvalidInstructionData { index: u32 },
+    #[error("mints of pool {pool} not found in token balances")]
+    UnresolvedMints { pool: String },
+    #[error("amounts of pool {pool} not found in transfers or token balances")]
+    UnresolvedAmounts { pool: String },
+    #[error("input or output of the {program} route in {signature} not resolved")]
+    UnresolvedRoute { program: String, signature: String },
+}
+
+impl DecodeError {
+    /// Stable key the error is counted under in the skipped sidecar files.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            DecodeError::MissingMeta => "missing_meta",
+            DecodeError::UndecodableTransaction => "undecodable_transaction",
+            DecodeError::MissingTokenBalances => "missing_token_balances",
+            DecodeError::MissingSigner => "missing_signer",
+            DecodeError::InvalidInstructionData { .. } => "invalid_instruction_data",
+            DecodeError::UnresolvedMints { .. } => "unresolved_mints",
+            DecodeError::UnresolvedAmounts { .. } => "unresolved_amounts",
+            DecodeError::UnresolvedRoute { .. } => "unresolved_route",
+        }
+    }
+}
@@ -20,3 +20,38 @@ lazy_static! {
         env::var("OUTPUT_PATH").expect("OUTPUT_PATH is not set")
     };
 }
+
+lazy_static! {
+    // also decode failed transactions into a separate dataset, off unless CAPTURE_FAILED_SWAPS is "true" or "1"
+    pub static ref CAPTURE_FAILED_SWAPS: bool = {
+        env::var("CAPTURE_FAILED_SWAPS")
+            .map(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+            .unwrap_or(false)
+    };
+}
+
+lazy_static! {
+    // mints that count as the quote side of a pair, highest priority first; QUOTE_PRIORITY takes a comma separated list
+    pub static ref QUOTE_PRIORITY: Vec<String> = {
+        env::var("QUOTE_PRIORITY")
+            .unwrap_or_else(|_| {
+                [
+                    "So11111111111111111111111111111111111111112",
+                    "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+                    "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB",
+                ]
+                .join(",")
+            })
+            .split(',')
+            .map(|mint| mint.trim().to_string())
+            .filter(|mint| !mint.is_empty())
+            .collect()
+    };
+}
+
+lazy_static! {
+    // transaction encoding blocks are fetched in: json (default), jsonParsed, base58 or base64
+    pub static ref BLOCK_ENCODING: String = {
+        env::var("BLOCK_ENCODING").unwrap_or_else(|_| "json".to_string())
+    };
+}
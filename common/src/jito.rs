@@ -0,0 +1,134 @@
+use solana_sdk::bs58;
+use solana_transaction_status::UiCompiledInstruction;
+
+use crate::models::InnerInstructions;
+
+pub const SYSTEM_PROGRAM_ID: &str = "11111111111111111111111111111111";
+
+/// Accounts the Jito block engine collects bundle tips in.
+pub const JITO_TIP_ACCOUNTS: [&str; 8] = [
+    "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5",
+    "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe",
+    "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY",
+    "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49",
+    "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh",
+    "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt",
+    "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL",
+    "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
+];
+
+const SYSTEM_TRANSFER: u32 = 2;
+
+/// Lamports a transaction sent to the Jito tip accounts through System
+/// program transfers, top-level or inner. 0 for transactions that didn't go
+/// through a bundle (or tipped some other way).
+pub fn jito_tip_lamports(
+    instructions: &[UiCompiledInstruction],
+    inners: &[InnerInstructions],
+    all_addresses: &[String],
+) -> u64 {
+    let top_level = instructions.iter().filter_map(|inst| {
+        let data = bs58::decode(&inst.data).into_vec().ok()?;
+        tip_amount(inst.program_id_index as usize, &inst.accounts, &data, all_addresses)
+    });
+    let inner = inners
+        .iter()
+        .flat_map(|inner| inner.instructions.iter())
+        .filter_map(|inst| {
+            tip_amount(inst.program_id_index as usize, &inst.accounts, &inst.data, all_addresses)
+        });
+
+    top_level.chain(inner).fold(0, u64::saturating_add)
+}
+
+/// Lamports moved by a System `Transfer` into a tip account, `None` for any
+/// other instruction.
+fn tip_amount(
+    program_id_index: usize,
+    accounts: &[u8],
+    data: &[u8],
+    all_addresses: &[String],
+) -> Option<u64> {
+    if all_addresses.get(program_id_index)? != SYSTEM_PROGRAM_ID {
+        return None;
+    }
+    let tag = u32::from_le_bytes(data.get(..4)?.try_into().ok()?);
+    if tag != SYSTEM_TRANSFER {
+        return None;
+    }
+    let recipient = all_addresses.get(*accounts.get(1)? as usize)?;
+    if !JITO_TIP_ACCOUNTS.contains(&recipient.as_str()) {
+        return None;
+    }
+    Some(u64::from_le_bytes(data.get(4..12)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::InnerInstruction;
+
+    use super::*;
+
+    fn accounts() -> Vec<String> {
+        [
+            SYSTEM_PROGRAM_ID,
+            "payer",
+            JITO_TIP_ACCOUNTS[3],
+            "someone",
+            "dex",
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    fn system_transfer(lamports: u64) -> Vec<u8> {
+        let mut data = SYSTEM_TRANSFER.to_le_bytes().to_vec();
+        data.extend(lamports.to_le_bytes());
+        data
+    }
+
+    fn top_level(program_id_index: u8, accounts: Vec<u8>, data: &[u8]) -> UiCompiledInstruction {
+        UiCompiledInstruction {
+            program_id_index,
+            accounts,
+            data: bs58::encode(data).into_string(),
+            stack_height: None,
+        }
+    }
+
+    #[test]
+    fn sums_top_level_and_inner_tips() {
+        let instructions = [
+            top_level(0, vec![1, 2], &system_transfer(10_000)),
+            top_level(4, vec![1], &[1]),
+        ];
+        let inners = [InnerInstructions {
+            index: 1,
+            instructions: vec![InnerInstruction {
+                program_id_index: 0,
+                accounts: vec![1, 2],
+                data: system_transfer(5_000),
+                stack_height: Some(2),
+            }],
+        }];
+        assert_eq!(
+            jito_tip_lamports(&instructions, &inners, &accounts()),
+            15_000
+        );
+    }
+
+    #[test]
+    fn ignores_other_transfers() {
+        // to an account that isn't a tip account
+        let to_someone = top_level(0, vec![1, 3], &system_transfer(10_000));
+        // not a System program instruction
+        let from_dex = top_level(4, vec![1, 2], &system_transfer(10_000));
+        // a System instruction other than `Transfer` (CreateAccount)
+        let mut create = 0u32.to_le_bytes().to_vec();
+        create.extend(10_000u64.to_le_bytes());
+        let create = top_level(0, vec![1, 2], &create);
+
+        let instructions = [to_someone, from_dex, create];
+        assert_eq!(jito_tip_lamports(&instructions, &[], &accounts()), 0);
+    }
+}
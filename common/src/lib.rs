@@ -1,8 +1,16 @@
+pub mod anchor_events;
 pub mod block_processor;
+pub mod compute_budget;
+pub mod errors;
 pub mod global;
+pub mod jito;
 pub mod models;
+pub mod program_logs;
+pub mod routes;
 pub mod rpc_client;
+pub mod sandwich;
 pub mod trade_parser;
+pub mod tx_encoding;
 pub mod tx_processor;
 pub mod utils;
 pub mod pricer;
\ No newline at end of file
@@ -1,7 +1,11 @@
+use std::collections::BTreeMap;
+
 use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 use solana_sdk::pubkey::Pubkey;
 
+use crate::errors::DecodeError;
+
 #[derive(Debug)]
 pub struct TradeInstruction {
     pub dapp_address: String,
@@ -10,6 +14,26 @@ pub struct TradeInstruction {
     pub amm: String,
     pub vault_a: String,
     pub vault_b: String,
+    /// Mints of the two vaults, left empty when they have to be resolved
+    /// from token balances.
+    pub mint_a: String,
+    pub mint_b: String,
+    /// Exact vault deltas (pool perspective) when the decoder can read them
+    /// from the instruction or its events; otherwise they're taken from the
+    /// instruction's token transfers or the token balance diff.
+    pub amount_a: Option<TokenAmount>,
+    pub amount_b: Option<TokenAmount>,
+    /// Pool fee in input token UI units, if the venue reports it.
+    pub fee: Option<f64>,
+    /// Pool price after the swap, quote (b) per base (a), if known.
+    pub post_price: Option<f64>,
+    /// Pool reserves before the swap in UI units, if the venue reports them.
+    pub reserve_a: Option<f64>,
+    pub reserve_b: Option<f64>,
+    /// Token-2022 transfer fees withheld on the vaults' transfers, raw units,
+    /// if the decoder knows them.
+    pub transfer_fee_a: Option<u64>,
+    pub transfer_fee_b: Option<u64>,
 }
 
 impl Default for TradeInstruction {
@@ -21,10 +45,134 @@ impl Default for TradeInstruction {
             amm: "".to_string(),
             vault_a: "".to_string(),
             vault_b: "".to_string(),
+            mint_a: "".to_string(),
+            mint_b: "".to_string(),
+            amount_a: None,
+            amount_b: None,
+            fee: None,
+            post_price: None,
+            reserve_a: None,
+            reserve_b: None,
+            transfer_fee_a: None,
+            transfer_fee_b: None,
         }
     }
 }
 
+/// An aggregator route as a whole, as read from the instruction. The legs
+/// are decoded separately by their venues' decoders.
+#[derive(Debug, Default)]
+pub struct RouteInstruction {
+    pub dapp_address: String,
+    pub name: String,
+    pub input_mint: String,
+    pub output_mint: String,
+    /// What the user paid in and got out, both positive.
+    pub amount_in: Option<TokenAmount>,
+    pub amount_out: Option<TokenAmount>,
+    /// Legs the aggregator reported and the programs they went through, in
+    /// execution order; 0 and empty when it reported none.
+    pub hop_count: u32,
+    pub venues: Vec<String>,
+}
+
+/// Whether liquidity went into a pool or came out of it.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum LiquidityAction {
+    #[default]
+    Add,
+    Remove,
+}
+
+impl LiquidityAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LiquidityAction::Add => "Add",
+            LiquidityAction::Remove => "Remove",
+        }
+    }
+}
+
+/// A liquidity deposit or withdrawal as read from the instruction.
+#[derive(Debug, Default)]
+pub struct LiquidityInstruction {
+    pub dapp_address: String,
+    pub name: String,
+    pub action: LiquidityAction,
+    pub amm: String,
+    /// Position account for concentrated liquidity, empty for LP-token pools.
+    pub position: String,
+    /// Vault `vault_b` is empty for single-sided deposits that only pass the
+    /// vault they pay into.
+    pub vault_a: String,
+    pub vault_b: String,
+    /// Left empty when they have to be resolved from token balances.
+    pub mint_a: String,
+    pub mint_b: String,
+    /// Exact vault deltas (pool perspective, positive for deposits) when the
+    /// decoder can read them, otherwise taken from transfers or balances.
+    pub amount_a: Option<TokenAmount>,
+    pub amount_b: Option<TokenAmount>,
+    pub lp_mint: String,
+    /// LP tokens minted or burnt, always positive.
+    pub lp_amount: Option<TokenAmount>,
+    /// Liquidity units added to or removed from a concentrated position.
+    pub liquidity: Option<u128>,
+}
+
+/// A pool initialization as read from the instruction.
+#[derive(Debug, Default)]
+pub struct PoolCreationInstruction {
+    pub dapp_address: String,
+    pub name: String,
+    pub amm: String,
+    /// Wallet that created and paid for the pool.
+    pub creator: String,
+    pub mint_a: String,
+    pub mint_b: String,
+    pub vault_a: String,
+    pub vault_b: String,
+    /// Empty for concentrated liquidity pools, which have no LP token.
+    pub lp_mint: String,
+    /// Initial deposit, when the decoder can read it from the instruction;
+    /// otherwise taken from the vaults' transfers or balances.
+    pub amount_a: Option<TokenAmount>,
+    pub amount_b: Option<TokenAmount>,
+    /// Opening price, b per a, for pools that are created with a price
+    /// rather than a deposit.
+    pub initial_price: Option<f64>,
+}
+
+/// A bonding curve handing its reserves over to an AMM, as read from the
+/// instruction.
+#[derive(Debug, Default)]
+pub struct MigrationInstruction {
+    pub dapp_address: String,
+    pub name: String,
+    pub mint: String,
+    pub curve: String,
+    /// Program and pool the reserves went to, when the instruction names
+    /// them; otherwise linked to a pool created later in the block.
+    pub destination_program: String,
+    pub destination_pool: String,
+    /// SOL and tokens taken out of the curve, positive.
+    pub sol_amount: Option<TokenAmount>,
+    pub token_amount: Option<TokenAmount>,
+}
+
+/// Signed amount in the token's base units.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TokenAmount {
+    pub raw: i128,
+    pub decimals: u32,
+}
+
+impl TokenAmount {
+    pub fn ui_amount(&self) -> f64 {
+        self.raw as f64 / 10f64.powi(self.decimals as i32)
+    }
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct UiTokenAmount {
     pub ui_amount: f64,
@@ -57,7 +205,7 @@ pub struct InnerInstructions {
     pub instructions: Vec<InnerInstruction>,
 }
 
-#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub struct TradeData {
     pub block_date: String,
     pub block_time: i64,
@@ -72,16 +220,341 @@ pub struct TradeData {
     pub quote_vault: String,
     pub base_amount: f64,
     pub quote_amount: f64,
+    /// `base_amount`/`quote_amount` in base units, without the float rounding.
+    #[serde(default, with = "raw_amount")]
+    pub base_amount_raw: i128,
+    #[serde(default, with = "raw_amount")]
+    pub quote_amount_raw: i128,
+    #[serde(default)]
+    pub base_decimals: u32,
+    #[serde(default)]
+    pub quote_decimals: u32,
+    /// What the transfers moved before Token-2022 transfer fees: a vault that
+    /// received `base_amount` was sent `base_amount + base_transfer_fee`, one
+    /// that paid out `base_amount` to the trader sent it plus the fee withheld
+    /// from the trader. Same as the net amounts for mints without a transfer
+    /// fee.
+    #[serde(default)]
+    pub base_amount_gross: f64,
+    #[serde(default)]
+    pub quote_amount_gross: f64,
+    #[serde(default)]
+    pub base_transfer_fee: f64,
+    #[serde(default)]
+    pub quote_transfer_fee: f64,
+    /// Pool fee in input token units, 0 when the venue doesn't report it.
+    #[serde(default)]
+    pub swap_fee: f64,
+    /// Pool price (quote per base) after the swap, 0 when unknown.
+    #[serde(default)]
+    pub post_swap_price: f64,
+    /// Pool reserves before the swap, 0 when the venue doesn't report them.
+    #[serde(default)]
+    pub base_reserve: f64,
+    #[serde(default)]
+    pub quote_reserve: f64,
+    /// Mint the signer paid into the pool and the one it got out, empty when
+    /// the two sides didn't move in opposite directions.
+    #[serde(default)]
+    pub input_mint: String,
+    #[serde(default)]
+    pub output_mint: String,
+    /// Both positive, in UI units.
+    #[serde(default)]
+    pub amount_in: f64,
+    #[serde(default)]
+    pub amount_out: f64,
+    /// "Buy" or "Sell" of the non-quote mint, the quote being whichever mint
+    /// of the pair ranks first in `QUOTE_PRIORITY`.
+    #[serde(default)]
+    pub side: String,
     pub is_inner_instruction: bool,
     pub instruction_index: u32,
     pub instruction_type: String,
     pub inner_instruction_index: u32,
+    /// CPI depth, 1 for top-level instructions, 0 when the block predates
+    /// stack heights.
+    #[serde(default)]
+    pub stack_height: u32,
     pub outer_program: String,
     pub inner_program: String,
+    /// Program that invoked the swap through CPI, empty for top-level swaps.
+    /// Differs from `outer_program` when the swap is nested more than one
+    /// level deep.
+    #[serde(default)]
+    pub invoking_program: String,
+    /// `{signature}:{instruction index}` shared by the legs of an aggregator
+    /// route, or of a multi-hop swap whose legs chain output mint to input
+    /// mint. Empty for standalone swaps.
+    #[serde(default)]
+    pub route_id: String,
     pub txn_fee_lamports: u64,
+    /// Part of `txn_fee_lamports` paid for priority: the compute unit price
+    /// times the requested limit.
+    #[serde(default)]
+    pub priority_fee_lamports: u64,
+    /// Compute units the transaction requested (or the default allowance when
+    /// it set none), the price it bid per unit and the units it used.
+    #[serde(default)]
+    pub cu_limit: u32,
+    #[serde(default)]
+    pub cu_price_micro_lamports: u64,
+    #[serde(default)]
+    pub cu_consumed: u64,
+    /// SOL the transaction tipped to Jito, non-zero for trades landed through
+    /// a bundle.
+    #[serde(default)]
+    pub jito_tip_lamports: u64,
+    /// Signer-owned token accounts that paid the pool's vaults or were paid
+    /// by them, followed by their owners.
+    #[serde(default, with = "account_list")]
+    pub trader_accounts: Vec<String>,
     pub signer_lamports_change: i64,
 }
 
+/// Net result of all swap legs in a transaction, from the signer's side:
+/// what went into the first leg and what came out of the last one.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RouteTrade {
+    pub block_date: String,
+    pub block_time: i64,
+    pub block_slot: u64,
+    pub signature: String,
+    pub signer: String,
+    pub input_mint: String,
+    pub output_mint: String,
+    pub input_amount: f64,
+    pub output_amount: f64,
+    #[serde(with = "raw_amount")]
+    pub input_amount_raw: i128,
+    #[serde(with = "raw_amount")]
+    pub output_amount_raw: i128,
+    pub input_decimals: u32,
+    pub output_decimals: u32,
+    pub hop_count: u32,
+    /// Programs of the venues the legs went through, in execution order.
+    pub venues: Vec<String>,
+}
+
+/// A swap instruction of a transaction that failed. Nothing moved, so there
+/// are no amounts; the error tells slippage failures and bot reverts apart.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FailedSwap {
+    pub block_date: String,
+    pub block_time: i64,
+    pub block_slot: u64,
+    pub signature: String,
+    pub signer: String,
+    pub pool_address: String,
+    /// Venue program the swap was sent to.
+    pub program: String,
+    pub instruction_type: String,
+    pub is_inner_instruction: bool,
+    pub instruction_index: u32,
+    pub inner_instruction_index: u32,
+    pub outer_program: String,
+    pub txn_fee_lamports: u64,
+    /// Top-level instruction that failed, -1 if the error isn't tied to one.
+    pub failed_instruction_index: i32,
+    /// Custom program error code (e.g. slippage exceeded), -1 for other errors.
+    pub error_code: i64,
+    pub error: String,
+}
+
+/// Raw amounts are written as decimal strings: neither Avro nor most CSV
+/// readers handle 128-bit integers.
+mod raw_amount {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(amount: &i128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&amount.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i128, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Account lists are written as one comma-separated string, which CSV rows
+/// can hold as a single column.
+mod account_list {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(accounts: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&accounts.join(","))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<String>, D::Error> {
+        Ok(String::deserialize(deserializer)?
+            .split(',')
+            .filter(|account| !account.is_empty())
+            .map(String::from)
+            .collect())
+    }
+}
+
+/// Tokens a provider put into or took out of a pool in one instruction.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LiquidityEvent {
+    pub block_date: String,
+    pub block_time: i64,
+    pub block_slot: u64,
+    pub signature: String,
+    pub signer: String,
+    pub pool_address: String,
+    /// Concentrated liquidity position, empty for LP-token pools.
+    pub position: String,
+    /// "Add" or "Remove".
+    pub action: String,
+    pub instruction_type: String,
+    pub base_mint: String,
+    pub quote_mint: String,
+    pub base_vault: String,
+    pub quote_vault: String,
+    /// Pool-perspective deltas, positive when added. The quote side of a
+    /// single-sided deposit is empty and 0.
+    pub base_amount: f64,
+    pub quote_amount: f64,
+    #[serde(with = "raw_amount")]
+    pub base_amount_raw: i128,
+    #[serde(with = "raw_amount")]
+    pub quote_amount_raw: i128,
+    pub base_decimals: u32,
+    pub quote_decimals: u32,
+    pub lp_mint: String,
+    /// LP tokens minted or burnt, 0 for concentrated liquidity.
+    pub lp_amount: f64,
+    /// Liquidity units of a concentrated position as a decimal string, empty
+    /// for LP-token pools.
+    pub liquidity: String,
+    pub is_inner_instruction: bool,
+    pub instruction_index: u32,
+    pub inner_instruction_index: u32,
+    pub outer_program: String,
+    pub inner_program: String,
+    pub txn_fee_lamports: u64,
+}
+
+/// A pool initialized on one of the supported venues.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PoolCreated {
+    pub block_date: String,
+    pub block_time: i64,
+    pub block_slot: u64,
+    pub signature: String,
+    pub creator: String,
+    pub program: String,
+    pub instruction_type: String,
+    pub pool_address: String,
+    pub base_mint: String,
+    pub quote_mint: String,
+    pub base_vault: String,
+    pub quote_vault: String,
+    pub lp_mint: String,
+    /// What the vaults held once the pool was set up, 0 for pools that open
+    /// empty.
+    pub base_reserve: f64,
+    pub quote_reserve: f64,
+    #[serde(with = "raw_amount")]
+    pub base_reserve_raw: i128,
+    #[serde(with = "raw_amount")]
+    pub quote_reserve_raw: i128,
+    pub base_decimals: u32,
+    pub quote_decimals: u32,
+    /// Quote per base, from the opening price or the initial reserves; 0 when
+    /// neither is known.
+    pub initial_price: f64,
+    pub is_inner_instruction: bool,
+    pub instruction_index: u32,
+    pub inner_instruction_index: u32,
+    /// Program that created the pool through CPI (a launchpad migrating a
+    /// token, say), empty for top-level instructions.
+    pub outer_program: String,
+}
+
+/// A token graduating from a launchpad bonding curve to an AMM pool, linking
+/// its trades on the curve to those on the pool.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Migration {
+    pub block_date: String,
+    pub block_time: i64,
+    pub block_slot: u64,
+    pub signature: String,
+    pub signer: String,
+    pub program: String,
+    pub instruction_type: String,
+    pub mint: String,
+    pub bonding_curve: String,
+    /// Empty when no pool for the mint was created by the end of the block.
+    pub destination_program: String,
+    pub destination_pool: String,
+    pub sol_amount: f64,
+    pub token_amount: f64,
+    #[serde(with = "raw_amount")]
+    pub sol_amount_raw: i128,
+    #[serde(with = "raw_amount")]
+    pub token_amount_raw: i128,
+    pub token_decimals: u32,
+    pub is_inner_instruction: bool,
+    pub instruction_index: u32,
+    pub inner_instruction_index: u32,
+}
+
+/// A trade sandwiched between two trades of one attacker on the same pool
+/// within a slot.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Sandwich {
+    pub block_date: String,
+    pub block_time: i64,
+    pub block_slot: u64,
+    pub pool_address: String,
+    pub attacker: String,
+    pub front_run_signature: String,
+    pub victim_signature: String,
+    pub back_run_signature: String,
+    pub victim_signer: String,
+    /// "Buy" or "Sell", the direction the front-run pushed the price in.
+    pub victim_side: String,
+    pub victim_input_mint: String,
+    pub victim_amount_in: f64,
+    pub quote_mint: String,
+    /// Estimated gross profit of the front-run and back-run together, in UI
+    /// units of `quote_mint`, over the part of the position the back-run
+    /// closed.
+    pub attacker_profit: f64,
+    /// The other mint of the pair, the one the attacker took a position in,
+    /// and how much of it the front-run bought (or sold).
+    pub position_mint: String,
+    pub position_amount: f64,
+    /// Part of the position the back-run didn't close, in UI units of
+    /// `position_mint`; negative when it unwound more than the front-run
+    /// took.
+    pub unmatched_amount: f64,
+}
+
+/// Swap instructions a block's decode pass skipped, counted by
+/// `DecodeError::reason`. A transaction that couldn't be unpacked at all
+/// counts once.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct SkippedCounts {
+    pub block_slot: u64,
+    pub counts: BTreeMap<String, u64>,
+}
+
+impl SkippedCounts {
+    pub fn record(&mut self, error: &DecodeError) {
+        *self.counts.entry(error.reason().to_string()).or_insert(0) += 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+}
+
 #[derive(Clone, PartialEq)]
 pub struct Output {
     pub data: Vec<TradeData>,
@@ -92,6 +565,28 @@ pub struct Transfer {
     pub amount: u64,
 }
 
+/// Token-2022 `TransferCheckedWithFee` arguments.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default)]
+pub struct TransferCheckedWithFee {
+    pub amount: u64,
+    pub decimals: u8,
+    pub fee: u64,
+}
+
+/// Net effect of a set of token transfers on one token account, raw units.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct TokenTransfer {
+    /// Balance change of the account, negative when it sent more than it got.
+    pub amount: i128,
+    /// Token-2022 transfer fees withheld on the transfers the account
+    /// received. Senders pay the gross amount and are charged no fee.
+    pub fee: u64,
+    /// Gross amount credited to the account by Token-2022 transfers that
+    /// don't state their fee (`Transfer`, `TransferChecked`). Part of it may
+    /// have been withheld if the mint has a transfer fee.
+    pub unstated_fee_credit: i128,
+}
+
 // TODO: This works but requires 1 extra call, the sama data can be parsed
 // out of the inner intructions/instructions from Raydium
 #[derive(Debug, BorshDeserialize, BorshSerialize)]
@@ -0,0 +1,155 @@
+/// Log lines of a single program invocation.
+pub struct InvocationLogs {
+    pub program_id: String,
+    /// Invoke depth, 1 for top-level instructions.
+    pub depth: u32,
+    /// Lines the program logged itself; lines of nested invocations go to
+    /// their own entry.
+    pub logs: Vec<String>,
+}
+
+/// Splits a transaction's log messages into one entry per program invocation,
+/// in the order the invocations started (i.e. execution order).
+pub fn split_invocations(log_messages: &[String]) -> Vec<InvocationLogs> {
+    let mut invocations: Vec<InvocationLogs> = vec![];
+    // indices into `invocations` of the programs currently executing
+    let mut stack: Vec<usize> = vec![];
+
+    for line in log_messages {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["Program", program_id, "invoke", depth] => {
+                let depth = depth
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse()
+                    .unwrap_or(stack.len() as u32 + 1);
+                stack.push(invocations.len());
+                invocations.push(InvocationLogs {
+                    program_id: program_id.to_string(),
+                    depth,
+                    logs: vec![],
+                });
+            }
+            ["Program", program_id, "success"] | ["Program", program_id, "failed:", ..]
+                if stack
+                    .last()
+                    .is_some_and(|&current| invocations[current].program_id == *program_id) =>
+            {
+                stack.pop();
+            }
+            _ => {
+                if let Some(&current) = stack.last() {
+                    invocations[current].logs.push(line.clone());
+                }
+            }
+        }
+    }
+
+    invocations
+}
+
+/// Pairs instructions, given by program id in execution order, with their
+/// invocation's logs. Instructions without an invoke line of their own
+/// (precompiles, logs cut off by the truncation limit) get no logs.
+pub fn match_invocations<'a>(
+    program_ids: &[&str],
+    invocations: &'a [InvocationLogs],
+) -> Vec<&'a [String]> {
+    let mut next = 0;
+    program_ids
+        .iter()
+        .map(|program_id| match invocations.get(next) {
+            Some(invocation) if invocation.program_id == *program_id => {
+                next += 1;
+                invocation.logs.as_slice()
+            }
+            _ => &[],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn split_invocations_keeps_nested_logs_apart() {
+        let logs = lines(&[
+            "Program ComputeBudget111111111111111111111111111111 invoke [1]",
+            "Program ComputeBudget111111111111111111111111111111 success",
+            "Program JUP invoke [1]",
+            "Program log: Instruction: Route",
+            "Program AMM invoke [2]",
+            "Program log: ray_log: AAAA",
+            "Program AMM consumed 2000 compute units",
+            "Program AMM success",
+            "Program data: event",
+            "Program JUP success",
+        ]);
+        let invocations = split_invocations(&logs);
+
+        let programs: Vec<(&str, u32)> = invocations
+            .iter()
+            .map(|invocation| (invocation.program_id.as_str(), invocation.depth))
+            .collect();
+        assert_eq!(
+            programs,
+            vec![
+                ("ComputeBudget111111111111111111111111111111", 1),
+                ("JUP", 1),
+                ("AMM", 2)
+            ]
+        );
+        assert!(invocations[0].logs.is_empty());
+        assert_eq!(
+            invocations[1].logs,
+            lines(&["Program log: Instruction: Route", "Program data: event"])
+        );
+        assert_eq!(
+            invocations[2].logs,
+            lines(&[
+                "Program log: ray_log: AAAA",
+                "Program AMM consumed 2000 compute units"
+            ])
+        );
+    }
+
+    #[test]
+    fn split_invocations_pops_failed_invocations() {
+        let logs = lines(&[
+            "Program A invoke [1]",
+            "Program B invoke [2]",
+            "Program B failed: custom program error: 0x1",
+            "Program log: back in A",
+            "Program A failed: custom program error: 0x1",
+        ]);
+        let invocations = split_invocations(&logs);
+        assert_eq!(invocations.len(), 2);
+        assert_eq!(invocations[0].logs, lines(&["Program log: back in A"]));
+    }
+
+    #[test]
+    fn match_invocations_skips_instructions_without_invoke_lines() {
+        let logs = lines(&[
+            "Program A invoke [1]",
+            "Program log: a",
+            "Program A success",
+            "Program B invoke [1]",
+            "Program log: b",
+            "Program B success",
+        ]);
+        let invocations = split_invocations(&logs);
+        let matched = match_invocations(&["A", "Precompile", "B", "C"], &invocations);
+
+        assert_eq!(matched.len(), 4);
+        assert_eq!(matched[0], lines(&["Program log: a"]).as_slice());
+        assert!(matched[1].is_empty());
+        assert_eq!(matched[2], lines(&["Program log: b"]).as_slice());
+        assert!(matched[3].is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::models::{RouteTrade, TradeData};
+
+/// One swap leg seen from the signer's side.
+struct Leg<'a> {
+    input_mint: &'a str,
+    input_raw: i128,
+    input_decimals: u32,
+    output_mint: &'a str,
+    output_raw: i128,
+    output_decimals: u32,
+    venue: &'a str,
+}
+
+impl<'a> Leg<'a> {
+    /// Amounts are pool deltas, so the side the pool received is what the
+    /// signer paid in. Rows that don't move both sides in opposite directions
+    /// aren't swaps and are skipped.
+    fn from_trade(trade: &'a TradeData) -> Option<Leg<'a>> {
+        let venue = if trade.inner_program.is_empty() {
+            &trade.outer_program
+        } else {
+            &trade.inner_program
+        };
+        if trade.base_amount_raw > 0 && trade.quote_amount_raw < 0 {
+            Some(Leg {
+                input_mint: &trade.base_mint,
+                input_raw: trade.base_amount_raw,
+                input_decimals: trade.base_decimals,
+                output_mint: &trade.quote_mint,
+                output_raw: -trade.quote_amount_raw,
+                output_decimals: trade.quote_decimals,
+                venue,
+            })
+        } else if trade.base_amount_raw < 0 && trade.quote_amount_raw > 0 {
+            Some(Leg {
+                input_mint: &trade.quote_mint,
+                input_raw: trade.quote_amount_raw,
+                input_decimals: trade.quote_decimals,
+                output_mint: &trade.base_mint,
+                output_raw: -trade.base_amount_raw,
+                output_decimals: trade.base_decimals,
+                venue,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Collapses the swap legs of one transaction into a single route record:
+/// the first leg's input and the last leg's output. Legs of split routes that
+/// start from the same mint, or end in the same one, are summed.
+///
+/// Only used for transactions that didn't go through an aggregator, whose
+/// route is taken from the aggregator instead (the trades hold the legs
+/// either way). `None` if the transaction has no legs.
+pub fn synthesize_route(trades: &[TradeData]) -> Option<RouteTrade> {
+    let legs: Vec<Leg> = trades.iter().filter_map(Leg::from_trade).collect();
+    let first = legs.first()?;
+    let last = legs.last()?;
+
+    let input_raw: i128 = legs
+        .iter()
+        .filter(|leg| leg.input_mint == first.input_mint)
+        .map(|leg| leg.input_raw)
+        .sum();
+    let output_raw: i128 = legs
+        .iter()
+        .filter(|leg| leg.output_mint == last.output_mint)
+        .map(|leg| leg.output_raw)
+        .sum();
+
+    let mut venues: Vec<String> = vec![];
+    for leg in legs.iter() {
+        if !venues.iter().any(|venue| venue == leg.venue) {
+            venues.push(leg.venue.to_string());
+        }
+    }
+
+    let trade = trades.first()?;
+    Some(RouteTrade {
+        block_date: trade.block_date.clone(),
+        block_time: trade.block_time,
+        block_slot: trade.block_slot,
+        signature: trade.signature.clone(),
+        signer: trade.signer.clone(),
+        input_mint: first.input_mint.to_string(),
+        output_mint: last.output_mint.to_string(),
+        input_amount: input_raw as f64 / 10f64.powi(first.input_decimals as i32),
+        output_amount: output_raw as f64 / 10f64.powi(last.output_decimals as i32),
+        input_amount_raw: input_raw,
+        output_amount_raw: output_raw,
+        input_decimals: first.input_decimals,
+        output_decimals: last.output_decimals,
+        hop_count: legs.len() as u32,
+        venues,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOL: &str = "So11111111111111111111111111111111111111112";
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const TOKEN: &str = "4k3Dyjzvzp8eMZWUXbBCjEvwSkkk59S5iCNLY3QrkX6R";
+
+    /// A leg with pool-side deltas: positive is what the pool received.
+    fn leg(base: (&str, i128), quote: (&str, i128), venue: &str) -> TradeData {
+        TradeData {
+            signature: String::from("sig"),
+            signer: String::from("signer"),
+            base_mint: base.0.to_string(),
+            base_amount_raw: base.1,
+            base_decimals: 6,
+            quote_mint: quote.0.to_string(),
+            quote_amount_raw: quote.1,
+            quote_decimals: 9,
+            outer_program: String::from("router"),
+            inner_program: venue.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn multi_hop_takes_first_input_and_last_output() {
+        let trades = [
+            leg((USDC, -150), (SOL, 1_000), "raydium"),
+            leg((TOKEN, -500), (USDC, 150), "orca"),
+        ];
+        let route = synthesize_route(&trades).unwrap();
+        assert_eq!(
+            (route.input_mint.as_str(), route.input_amount_raw),
+            (SOL, 1_000)
+        );
+        assert_eq!(
+            (route.output_mint.as_str(), route.output_amount_raw),
+            (TOKEN, 500)
+        );
+        assert_eq!(route.hop_count, 2);
+        assert_eq!(route.venues, vec!["raydium", "orca"]);
+    }
+
+    #[test]
+    fn split_legs_are_summed() {
+        let trades = [
+            leg((TOKEN, -300), (SOL, 600), "raydium"),
+            leg((TOKEN, -200), (SOL, 400), "raydium"),
+        ];
+        let route = synthesize_route(&trades).unwrap();
+        assert_eq!(route.input_amount_raw, 1_000);
+        assert_eq!(route.output_amount_raw, 500);
+        assert_eq!(route.input_amount, 1e-6);
+        assert_eq!(route.venues, vec!["raydium"]);
+    }
+
+    #[test]
+    fn rows_that_arent_swaps_are_skipped() {
+        let not_a_swap = leg((TOKEN, 300), (SOL, 600), "raydium");
+        assert!(synthesize_route(std::slice::from_ref(&not_a_swap)).is_none());
+
+        let top_level = TradeData {
+            outer_program: String::from("pump"),
+            inner_program: String::new(),
+            ..leg((TOKEN, -500), (SOL, 1_000), "")
+        };
+        let route = synthesize_route(&[not_a_swap, top_level]).unwrap();
+        assert_eq!(route.hop_count, 1);
+        assert_eq!(route.venues, vec!["pump"]);
+    }
+}
@@ -1,6 +1,6 @@
 use std::str::FromStr;
 
-use crate::global::RPC_CLIENT;
+use crate::global::{BLOCK_ENCODING, RPC_CLIENT};
 use anyhow::{Error, Result};
 use serde_json::json;
 use solana_client::{client_error::ClientError, rpc_client::RpcClient};
@@ -20,7 +20,8 @@ pub async fn fetch_block_with_version(block_slot: u64) -> Result<EncodedConfirme
     let params = json!([
         block_slot,
         { "maxSupportedTransactionVersion": 0 ,
-          "commitment": CommitmentLevel::Confirmed }
+          "commitment": CommitmentLevel::Confirmed,
+          "encoding": BLOCK_ENCODING.as_str() }
     ]);
 
     let response: serde_json::Value =
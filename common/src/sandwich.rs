@@ -0,0 +1,253 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::models::{Sandwich, TradeData};
+
+/// What the signer of a trade paid into the pool (negative) or got out of it
+/// (positive), in quote UI units, along with the quote mint.
+fn quote_flow(trade: &TradeData) -> (&str, f64) {
+    if trade.side == "Buy" {
+        (&trade.input_mint, -trade.amount_in)
+    } else {
+        (&trade.output_mint, trade.amount_out)
+    }
+}
+
+/// What the signer of a trade got of the non-quote mint (positive) or sold of
+/// it (negative), in UI units, along with the mint.
+fn position_flow(trade: &TradeData) -> (&str, f64) {
+    if trade.side == "Buy" {
+        (&trade.output_mint, trade.amount_out)
+    } else {
+        (&trade.input_mint, -trade.amount_in)
+    }
+}
+
+/// Whether two trades were made by the same trader: the same signer, or a
+/// signer-owned token account or owner in common, which catches bots that
+/// rotate fee payers over one wallet.
+fn same_trader(a: &TradeData, b: &TradeData) -> bool {
+    a.signer == b.signer
+        || a.trader_accounts
+            .iter()
+            .any(|account| b.trader_accounts.contains(account))
+}
+
+/// Finds sandwiches among a block's trades, given in execution order: a
+/// front-run and a back-run on the same pool by the same trader, in opposite
+/// directions, with trades of other traders in the front-run's direction
+/// between them. Each victim trade gets its own record.
+///
+/// A trader is matched by signer or by the token accounts it traded from
+/// (see `same_trader`), so legs sent from different signers still pair up.
+///
+/// Profit is the quote the back-run got out less what the front-run paid in
+/// (or the reverse for a sell-first sandwich), before transaction fees and
+/// tips. When the back-run doesn't unwind exactly the position the front-run
+/// took, both legs are scaled down to the matched part and the difference is
+/// reported as `unmatched_amount`.
+pub fn detect_sandwiches(trades: &[TradeData]) -> Vec<Sandwich> {
+    // pool -> its trades in execution order; rows without a pool or a
+    // direction can't be part of a sandwich
+    let mut pools: HashMap<&str, Vec<(usize, &TradeData)>> = HashMap::new();
+    for (idx, trade) in trades.iter().enumerate() {
+        if trade.pool_address.is_empty() || trade.side.is_empty() {
+            continue;
+        }
+        pools.entry(&trade.pool_address).or_default().push((idx, trade));
+    }
+
+    // keyed by the block positions of the front-run and the victim
+    let mut sandwiches: Vec<(usize, usize, Sandwich)> = vec![];
+    for pool_trades in pools.values() {
+        // indices already used as a front- or back-run
+        let mut used: HashSet<usize> = HashSet::new();
+        for (front_idx, &(front_pos, front)) in pool_trades.iter().enumerate() {
+            if used.contains(&front_idx) {
+                continue;
+            }
+            let back = pool_trades
+                .iter()
+                .enumerate()
+                .skip(front_idx + 1)
+                .find(|(idx, (_, trade))| {
+                    !used.contains(idx)
+                        && same_trader(trade, front)
+                        && trade.signature != front.signature
+                        && trade.side != front.side
+                });
+            let Some((back_idx, &(_, back))) = back else {
+                continue;
+            };
+            let victims: Vec<(usize, &TradeData)> = pool_trades[front_idx + 1..back_idx]
+                .iter()
+                .filter(|(_, trade)| !same_trader(trade, front) && trade.side == front.side)
+                .copied()
+                .collect();
+            if victims.is_empty() {
+                continue;
+            }
+            used.insert(front_idx);
+            used.insert(back_idx);
+
+            // profit only counts the part of the position the back-run closed
+            let (quote_mint, front_flow) = quote_flow(front);
+            let (_, back_flow) = quote_flow(back);
+            let (position_mint, opened) = position_flow(front);
+            let (_, closed) = position_flow(back);
+            let (opened, closed) = (opened.abs(), closed.abs());
+            let matched = opened.min(closed);
+            let share = |amount: f64| if amount > 0.0 { matched / amount } else { 0.0 };
+            let attacker_profit = front_flow * share(opened) + back_flow * share(closed);
+            for (victim_pos, victim) in victims {
+                let sandwich = Sandwich {
+                    block_date: front.block_date.clone(),
+                    block_time: front.block_time,
+                    block_slot: front.block_slot,
+                    pool_address: front.pool_address.clone(),
+                    attacker: front.signer.clone(),
+                    front_run_signature: front.signature.clone(),
+                    victim_signature: victim.signature.clone(),
+                    back_run_signature: back.signature.clone(),
+                    victim_signer: victim.signer.clone(),
+                    victim_side: victim.side.clone(),
+                    victim_amount_in: victim.amount_in,
+                    victim_input_mint: victim.input_mint.clone(),
+                    quote_mint: quote_mint.to_string(),
+                    attacker_profit,
+                    position_mint: position_mint.to_string(),
+                    position_amount: opened,
+                    unmatched_amount: opened - closed,
+                };
+                sandwiches.push((front_pos, victim_pos, sandwich));
+            }
+        }
+    }
+
+    sandwiches.sort_by_key(|(front_pos, victim_pos, _)| (*front_pos, *victim_pos));
+    sandwiches
+        .into_iter()
+        .map(|(_, _, sandwich)| sandwich)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOL: &str = "So11111111111111111111111111111111111111112";
+    const TOKEN: &str = "4k3Dyjzvzp8eMZWUXbBCjEvwSkkk59S5iCNLY3QrkX6R";
+
+    /// A trade on one pool: buys pay SOL for the token, sells the reverse.
+    fn trade(
+        signature: &str,
+        signer: &str,
+        side: &str,
+        amount_in: f64,
+        amount_out: f64,
+    ) -> TradeData {
+        let (input_mint, output_mint) = if side == "Buy" {
+            (SOL, TOKEN)
+        } else {
+            (TOKEN, SOL)
+        };
+        TradeData {
+            signature: signature.to_string(),
+            signer: signer.to_string(),
+            pool_address: String::from("pool"),
+            side: side.to_string(),
+            input_mint: input_mint.to_string(),
+            output_mint: output_mint.to_string(),
+            amount_in,
+            amount_out,
+            ..Default::default()
+        }
+    }
+
+    fn with_accounts(trade: TradeData, accounts: &[&str]) -> TradeData {
+        TradeData {
+            trader_accounts: accounts.iter().map(|account| account.to_string()).collect(),
+            ..trade
+        }
+    }
+
+    #[test]
+    fn buy_first_sandwich() {
+        let trades = [
+            trade("front", "bot", "Buy", 10.0, 100.0),
+            trade("victim", "user", "Buy", 5.0, 40.0),
+            trade("back", "bot", "Sell", 100.0, 11.0),
+        ];
+        let sandwiches = detect_sandwiches(&trades);
+        assert_eq!(sandwiches.len(), 1);
+        let sandwich = &sandwiches[0];
+        assert_eq!(sandwich.attacker, "bot");
+        assert_eq!(sandwich.victim_signature, "victim");
+        assert_eq!(sandwich.quote_mint, SOL);
+        assert_eq!(sandwich.position_mint, TOKEN);
+        assert_eq!(sandwich.position_amount, 100.0);
+        assert_eq!(sandwich.unmatched_amount, 0.0);
+        assert!((sandwich.attacker_profit - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn legs_tied_by_trader_accounts_across_signers() {
+        let trades = [
+            with_accounts(
+                trade("front", "payer-1", "Buy", 10.0, 100.0),
+                &["bot-ata", "bot"],
+            ),
+            with_accounts(
+                trade("victim", "user", "Buy", 5.0, 40.0),
+                &["user-ata", "user"],
+            ),
+            with_accounts(
+                trade("back", "payer-2", "Sell", 100.0, 11.0),
+                &["bot-ata", "bot"],
+            ),
+        ];
+        let sandwiches = detect_sandwiches(&trades);
+        assert_eq!(sandwiches.len(), 1);
+        assert_eq!(sandwiches[0].attacker, "payer-1");
+        assert_eq!(sandwiches[0].back_run_signature, "back");
+    }
+
+    #[test]
+    fn partial_close_scales_profit_and_reports_remainder() {
+        let trades = [
+            trade("front", "bot", "Buy", 10.0, 100.0),
+            trade("victim", "user", "Buy", 5.0, 40.0),
+            trade("back", "bot", "Sell", 50.0, 6.0),
+        ];
+        let sandwich = &detect_sandwiches(&trades)[0];
+        // half the position closed: half the SOL paid in against all of the SOL out
+        assert!((sandwich.attacker_profit - 1.0).abs() < 1e-9);
+        assert_eq!(sandwich.unmatched_amount, 50.0);
+    }
+
+    #[test]
+    fn no_sandwich_without_a_victim_of_another_trader() {
+        let trades = [
+            trade("front", "bot", "Buy", 10.0, 100.0),
+            trade("back", "bot", "Sell", 100.0, 11.0),
+        ];
+        assert!(detect_sandwiches(&trades).is_empty());
+
+        // the trade in between is the bot's own, under another fee payer
+        let trades = [
+            with_accounts(trade("front", "payer-1", "Buy", 10.0, 100.0), &["bot"]),
+            with_accounts(trade("middle", "payer-3", "Buy", 5.0, 40.0), &["bot"]),
+            with_accounts(trade("back", "payer-2", "Sell", 100.0, 11.0), &["bot"]),
+        ];
+        assert!(detect_sandwiches(&trades).is_empty());
+    }
+
+    #[test]
+    fn victim_must_trade_in_the_front_run_direction() {
+        let trades = [
+            trade("front", "bot", "Buy", 10.0, 100.0),
+            trade("other", "user", "Sell", 40.0, 5.0),
+            trade("back", "bot", "Sell", 100.0, 11.0),
+        ];
+        assert!(detect_sandwiches(&trades).is_empty());
+    }
+}
@@ -1,250 +1,534 @@
-use solana_transaction_status::UiInstruction;
-
-use crate::models::{TokenBalance, TradeInstruction};
-use crate::utils::prepare_input_accounts;
-
-const SWAP_WITH_PARTNER_DISCRIMINATOR: u64 = u64::from_le_bytes([248, 198, 158, 145, 225, 117, 135, 200]);
-const SWAP_DISCRIMINATOR: u64 = u64::from_le_bytes([248, 198, 158, 145, 225, 117, 135, 200]);
-const SWAP_EXACT_OUT_DISCRIMINATOR: u64 = u64::from_le_bytes([250, 73, 101, 33, 38, 207, 75, 184]);
-const SWAP_WITH_PRICE_IMPACT_DISCRIMINATOR: u64 =
-    u64::from_le_bytes([56, 173, 230, 208, 173, 228, 156, 205]);
-
-fn parse_raydium_trade_instruction(
-    bytes_stream: &Vec<u8>,
-    input_accounts: Vec<String>,
-    post_token_balances: &Vec<TokenBalance>,
-    accounts: &Vec<String>,
-    base_address: &String,
-    quote_address: &String,
-) -> Option<TradeInstruction> {
-    let (disc_bytes, rest) = bytes_stream.split_at(1);
-    let discriminator: u8 = u8::from(disc_bytes[0]);
-
-    let mut result = None;
-
-    match discriminator {
-        9 => {
-            result = Some(TradeInstruction {
-                dapp_address: String::from("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8"),
-                dex: String::from("RAYDIUM"),
-                name: String::from("SwapBaseIn"),
-                amm: input_accounts.get(1).unwrap().to_string(),
-                vault_a: base_address.to_string(),
-                vault_b: quote_address.to_string(),
-                ..Default::default()
-            });
-        }
-        11 => {
-            result = Some(TradeInstruction {
-                dapp_address: String::from("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8"),
-                dex: String::from("RAYDIUM"),
-                name: String::from("SwapBaseOut"),
-                amm: input_accounts.get(1).unwrap().to_string(),
-                vault_a: base_address.to_string(),
-                vault_b: quote_address.to_string(),
-                ..Default::default()
-            });
+mod jupiter;
+mod meteora;
+mod meteora_dlmm;
+mod moonshot;
+mod orca;
+mod pump_fun;
+mod pump_swap;
+mod raydium;
+mod raydium_clmm;
+mod raydium_cpmm;
+
+use std::collections::HashMap;
+
+use borsh::BorshDeserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::anchor_events::{find_event_cpis, find_event_logs};
+use crate::global::QUOTE_PRIORITY;
+use crate::models::{
+    InnerInstruction, LiquidityInstruction, MigrationInstruction, PoolCreationInstruction,
+    RouteInstruction, TokenAmount, TokenBalance, TradeInstruction,
+};
+use crate::utils::{get_token_transfer, get_transfer_counterparties};
+
+pub use jupiter::{JupiterDecoder, JUPITER_PROGRAM_ID};
+pub use meteora::{MeteoraDecoder, METEORA_PROGRAM_ID};
+pub use meteora_dlmm::{MeteoraDlmmDecoder, METEORA_DLMM_PROGRAM_ID};
+pub use moonshot::{MoonshotDecoder, MOONSHOT_PROGRAM_ID};
+pub use orca::{OrcaDecoder, ORCA_PROGRAM_ID};
+pub use pump_fun::{PumpFunDecoder, PUMP_FUN_PROGRAM_ID};
+pub use pump_swap::{PumpSwapDecoder, PUMP_SWAP_PROGRAM_ID};
+pub use raydium::{RaydiumDecoder, RAYDIUM_PROGRAM_ID};
+pub use raydium_clmm::{RaydiumClmmDecoder, RAYDIUM_CLMM_PROGRAM_ID};
+pub use raydium_cpmm::{RaydiumCpmmDecoder, RAYDIUM_CPMM_PROGRAM_ID};
+
+/// Everything a decoder gets to see about a single (top-level or inner) instruction.
+pub struct DecodeContext<'a> {
+    /// Raw instruction data, discriminator included.
+    pub data: &'a [u8],
+    /// Addresses of the accounts passed to the instruction, in order.
+    pub input_accounts: Vec<String>,
+    /// All account keys of the transaction, loaded addresses included.
+    pub accounts: &'a Vec<String>,
+    /// Accounts that signed the transaction, fee payer first.
+    pub signers: &'a [String],
+    pub pre_token_balances: &'a Vec<TokenBalance>,
+    pub post_token_balances: &'a Vec<TokenBalance>,
+    /// Lamport balances, indexed like `accounts`.
+    pub pre_balances: &'a Vec<u64>,
+    pub post_balances: &'a Vec<u64>,
+    /// Inner instructions executed underneath this instruction (self-CPI
+    /// events, token transfers, ...).
+    pub inner_instructions: &'a [InnerInstruction],
+    /// Log lines the program emitted for this instruction, nested invocations
+    /// excluded. Empty when the transaction has no logs.
+    pub logs: &'a [String],
+    /// Whether the instruction was invoked through CPI.
+    pub is_inner: bool,
+}
+
+/// Decoder for the swap instructions of a single DEX program.
+///
+/// To add a protocol, implement this trait in a new module next to the existing
+/// ones and register it in `DecoderRegistry::default`.
+pub trait DexDecoder: Send + Sync {
+    /// Program id the decoder handles.
+    fn program_id(&self) -> &'static str;
+
+    /// Decodes the instruction into a trade leg, `None` if it isn't a swap.
+    fn decode(&self, ctx: &DecodeContext) -> Option<TradeInstruction>;
+
+    /// Decodes every leg of the instruction. Multi-hop instructions override
+    /// this, everything else is a single `decode`d leg.
+    fn decode_legs(&self, ctx: &DecodeContext) -> Vec<TradeInstruction> {
+        self.decode(ctx).into_iter().collect()
+    }
+
+    /// Resolves the (base, quote) mints of a decoded leg. Defaults to the mints
+    /// the decoder read from the instruction, falling back to looking up the
+    /// vaults in the token balances.
+    fn resolve_mints(
+        &self,
+        trade: &TradeInstruction,
+        ctx: &DecodeContext,
+    ) -> Option<(String, String)> {
+        let base_mint = resolve_mint(&trade.mint_a, &trade.vault_a, ctx)?;
+        let quote_mint = resolve_mint(&trade.mint_b, &trade.vault_b, ctx)?;
+        Some((base_mint, quote_mint))
+    }
+
+    /// Decodes the instruction as a liquidity deposit or withdrawal, `None`
+    /// if it isn't one. Venues without liquidity instructions keep the
+    /// default.
+    fn decode_liquidity(&self, _ctx: &DecodeContext) -> Option<LiquidityInstruction> {
+        None
+    }
+
+    /// Decodes the instruction as a pool initialization, `None` if it isn't
+    /// one.
+    fn decode_pool_creation(&self, _ctx: &DecodeContext) -> Option<PoolCreationInstruction> {
+        None
+    }
+
+    /// Decodes an aggregator instruction as a whole route, `None` if it isn't
+    /// one. Aggregators decode no trade legs of their own: the legs are the
+    /// venue instructions they invoke.
+    fn decode_route(&self, _ctx: &DecodeContext) -> Option<RouteInstruction> {
+        None
+    }
+
+    /// Decodes the instruction as a launchpad curve migrating to an AMM,
+    /// `None` if it isn't one.
+    fn decode_migration(&self, _ctx: &DecodeContext) -> Option<MigrationInstruction> {
+        None
+    }
+}
+
+pub(crate) const WSOL_MINT: &str = "So11111111111111111111111111111111111111112";
+pub(crate) const SOL_DECIMALS: u32 = 9;
+
+/// Program id -> decoder lookup used by `process_tx`.
+pub struct DecoderRegistry {
+    decoders: HashMap<&'static str, Box<dyn DexDecoder>>,
+}
+
+impl DecoderRegistry {
+    pub fn new() -> Self {
+        DecoderRegistry {
+            decoders: HashMap::new(),
         }
-        _ => {}
     }
 
-    return result;
+    /// Registers a decoder, replacing any previous one for the same program id.
+    pub fn register(&mut self, decoder: Box<dyn DexDecoder>) {
+        self.decoders.insert(decoder.program_id(), decoder);
+    }
+
+    pub fn get(&self, program_id: &str) -> Option<&dyn DexDecoder> {
+        self.decoders.get(program_id).map(|decoder| decoder.as_ref())
+    }
 }
 
-fn parse_meteora_trade_instruction(
-    bytes_stream: &Vec<u8>,
-    accounts: &Vec<String>,
-) -> Option<TradeInstruction>{
-    let (disc_bytes, rest) = bytes_stream.split_at(8);
-    let disc_bytes_arr: [u8; 8] = disc_bytes.to_vec().try_into().unwrap();
-    let discriminator: u64 = u64::from_le_bytes(disc_bytes_arr);
+impl Default for DecoderRegistry {
+    fn default() -> Self {
+        let mut registry = DecoderRegistry::new();
+        registry.register(Box::new(RaydiumDecoder));
+        registry.register(Box::new(RaydiumClmmDecoder));
+        registry.register(Box::new(RaydiumCpmmDecoder));
+        registry.register(Box::new(MeteoraDecoder));
+        registry.register(Box::new(MeteoraDlmmDecoder));
+        registry.register(Box::new(OrcaDecoder));
+        registry.register(Box::new(PumpFunDecoder));
+        registry.register(Box::new(PumpSwapDecoder));
+        registry.register(Box::new(MoonshotDecoder));
+        registry.register(Box::new(JupiterDecoder));
+        registry
+    }
+}
 
-    let mut result: Option<TradeInstruction> = None;
+lazy_static::lazy_static! {
+    pub static ref DECODERS: DecoderRegistry = DecoderRegistry::default();
+}
 
-    
+/// Reads the 8-byte Anchor discriminator at the start of the instruction data.
+pub(crate) fn anchor_discriminator(data: &[u8]) -> Option<u64> {
+    let disc_bytes: [u8; 8] = data.get(..8)?.try_into().ok()?;
+    Some(u64::from_le_bytes(disc_bytes))
+}
 
-    match discriminator {
-        SWAP_WITH_PARTNER_DISCRIMINATOR => {
-            result = Some(TradeInstruction {
-                dapp_address: String::from("Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB"),
-                dex: String::from("METEORA"),
-                name: String::from("Swap"),
-                amm: accounts.get(0).unwrap().to_string(),
-                vault_a: accounts.get(5).unwrap().to_string(),
-                vault_b: accounts.get(6).unwrap().to_string(),
-                ..Default::default()
-            });
-        },
-        _ => {}
+/// Self-CPI events with the given discriminator emitted by `program_id`
+/// underneath the instruction being decoded, in emission order.
+pub(crate) fn cpi_events<T: BorshDeserialize>(
+    ctx: &DecodeContext,
+    program_id: &str,
+    discriminator: u64,
+) -> Vec<T> {
+    find_event_cpis(ctx.inner_instructions, ctx.accounts, program_id, discriminator)
+}
+
+/// `emit!` events with the given discriminator the program logged while
+/// running the instruction being decoded, in emission order.
+pub(crate) fn log_events<T: BorshDeserialize>(ctx: &DecodeContext, discriminator: u64) -> Vec<T> {
+    find_event_logs(ctx.logs, discriminator)
+}
+
+fn find_mint(vault: &str, token_balances: &[TokenBalance]) -> Option<String> {
+    token_balances
+        .iter()
+        .find(|balance| balance.address == vault)
+        .map(|balance| balance.mint.clone())
+}
+
+/// The mint a decoder read from the instruction, or the vault's mint from the
+/// token balances when it left it empty.
+pub(crate) fn resolve_mint(mint: &str, vault: &str, ctx: &DecodeContext) -> Option<String> {
+    if mint.is_empty() {
+        find_mint(vault, ctx.post_token_balances)
+            .or_else(|| find_mint(vault, ctx.pre_token_balances))
+    } else {
+        Some(mint.to_string())
     }
+}
 
-    return result;
+/// Puts a swap's input and output `(vault, mint)` sides in the order of pools
+/// that require `mint_0 < mint_1` at creation, so such a pool reports the same
+/// base and quote whichever way it's traded. Sides stay in swap order when a
+/// mint is unknown.
+pub(crate) fn in_pool_order(
+    input: (String, String),
+    output: (String, String),
+) -> ((String, String), (String, String)) {
+    match (input.1.parse::<Pubkey>(), output.1.parse::<Pubkey>()) {
+        (Ok(input_mint), Ok(output_mint)) if output_mint < input_mint => (output, input),
+        _ => (input, output),
+    }
 }
 
-fn parse_meteora_dlmm_trade_instruction(
-    bytes_stream: &Vec<u8>,
-    input_accounts: Vec<String>,
-) -> Option<TradeInstruction> {
-    let (disc_bytes, rest) = bytes_stream.split_at(8);
-    let disc_bytes_arr: [u8; 8] = disc_bytes.to_vec().try_into().unwrap();
-    let discriminator: u64 = u64::from_le_bytes(disc_bytes_arr);
+fn find_balance<'b>(token_account: &str, balances: &'b [TokenBalance]) -> Option<&'b TokenBalance> {
+    balances
+        .iter()
+        .find(|balance| balance.address == token_account)
+}
 
-    let mut result = None;
+/// Decimals of a token account's mint, as reported in its token balances.
+pub(crate) fn token_decimals(token_account: &str, token_balances: &[TokenBalance]) -> Option<u32> {
+    token_balances
+        .iter()
+        .find(|balance| balance.address == token_account)
+        .map(|balance| balance.ui_token_amount.decimals)
+}
 
-    match discriminator {
-        SWAP_DISCRIMINATOR => {
-            result = Some(TradeInstruction {
-                dapp_address: String::from("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo"),
-                dex: String::from("METEORA"),
-                name: String::from("Swap"),
-                amm: input_accounts.get(0).unwrap().to_string(),
-                vault_a: input_accounts.get(2).unwrap().to_string(),
-                vault_b: input_accounts.get(3).unwrap().to_string(),
-                ..Default::default()
-            });
-        },
-        SWAP_EXACT_OUT_DISCRIMINATOR => {
-            result = Some(TradeInstruction {
-                dapp_address: String::from("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo"),
-                dex: String::from("METEORA"),
-                name: String::from("SwapExactOut"),
-                amm: input_accounts.get(0).unwrap().to_string(),
-                vault_a: input_accounts.get(2).unwrap().to_string(),
-                vault_b: input_accounts.get(3).unwrap().to_string(),
-                ..Default::default()
-            });
-        },
-        SWAP_WITH_PRICE_IMPACT_DISCRIMINATOR => {
-            result = Some(TradeInstruction {
-                dapp_address: String::from("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo"),
-                dex: String::from("METEORA"),
-                name: String::from("SwapWithPriceImpact"),
-                amm: input_accounts.get(0).unwrap().to_string(),
-                vault_a: input_accounts.get(2).unwrap().to_string(),
-                vault_b: input_accounts.get(3).unwrap().to_string(),
-                ..Default::default()
-            });
-        },
-        _ => {}
-    }
-
-    return result;
-}
-
-fn parse_orca_trade_instruction(
-    bytes_stream: &Vec<u8>,
-    accounts: &Vec<String>,
-) -> Option<TradeInstruction> {
-    let (disc_bytes, rest) = bytes_stream.split_at(8);
-    let disc_bytes_arr: [u8; 8] = disc_bytes.to_vec().try_into().unwrap();
-    let discriminator: u64 = u64::from_le_bytes(disc_bytes_arr);
-
-    let mut result = None;
-
-    match discriminator {
-        14449647541112719096 => {
-            result = Some(TradeInstruction {
-                dapp_address: String::from("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc"),
-                dex: String::from("ORCA"),
-                name: String::from("Swap"),
-                amm: accounts.get(2).unwrap().to_string(),
-                vault_a: accounts.get(4).unwrap().to_string(),
-                vault_b: accounts.get(6).unwrap().to_string(),
-                ..Default::default()
-            });
-        }, 
-        7070309578724672555 => {
-            result = Some(TradeInstruction {
-                dapp_address: String::from("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc"),
-                dex: String::from("ORCA"),
-                name: String::from("SwapV2"),
-                amm: accounts.get(4).unwrap().to_string(),
-                vault_a: accounts.get(8).unwrap().to_string(),
-                vault_b: accounts.get(10).unwrap().to_string(),
-                ..Default::default()
-            });
-        }, 
-        16635068063392030915 => {
-            result = Some(TradeInstruction {
-                dapp_address: String::from("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc"),
-                dex: String::from("ORCA"),
-                name: String::from("TwoHopSwap"),
-                amm: accounts.get(2).unwrap().to_string(),
-                vault_a: accounts.get(5).unwrap().to_string(),
-                vault_b: accounts.get(7).unwrap().to_string(),
-                // second_swap_amm: Some(accounts.get(3).unwrap().to_string()),
-                // second_swap_vault_a: Some(accounts.get(9).unwrap().to_string()),
-                // second_swap_vault_b: Some(accounts.get(11).unwrap().to_string()),
-                ..Default::default()
-            });
-        },
-        8485347938364657594 => {
-            result = Some(TradeInstruction {
-                dapp_address: String::from("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc"),
-                dex: String::from("ORCA"),
-                name: String::from("TwoHopSwapV2"),
-                amm: accounts.get(0).unwrap().to_string(),
-                vault_a: accounts.get(9).unwrap().to_string(),
-                vault_b: accounts.get(10).unwrap().to_string(),
-                // second_swap_amm: Some(accounts.get(1).unwrap().to_string()),
-                // second_swap_vault_a: Some(accounts.get(11).unwrap().to_string()),
-                // second_swap_vault_b: Some(accounts.get(12).unwrap().to_string()),
-                ..Default::default()
-            });
-        },
-        _ => {}
-    }
-
-
-    return result
-}
-
-pub fn get_trade_instruction(
-    address: &String,
-    instruction_data: &Vec<u8>,
-    account_indices: &Vec<u8>,
-    accounts: &Vec<String>,
-    pre_token_balances: &Vec<TokenBalance>,
-    post_token_balances: &Vec<TokenBalance>,
-    outer_program: &String,
-    is_inner: bool,
-    input_inner_idx: u32,
-    base_address: &String,
-    quote_address: &String,
-) -> Option<TradeInstruction> {
-    let input_accounts = prepare_input_accounts(account_indices, accounts);
-    let mut result = None;
-    match address.as_str() {
-        "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8" => {
-            result = parse_raydium_trade_instruction(
-                &instruction_data,
-                input_accounts,
-                &post_token_balances,
-                accounts,
-                base_address,
-                quote_address,
-            );
-        },
-        "Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB" => {
-            result = parse_meteora_trade_instruction(
-                &instruction_data,
-                &input_accounts,
-            );
+/// Decimals of a mint, taken from any token balance of the transaction.
+pub(crate) fn mint_decimals(mint: &str, ctx: &DecodeContext) -> Option<u32> {
+    ctx.post_token_balances
+        .iter()
+        .chain(ctx.pre_token_balances.iter())
+        .find(|balance| balance.mint == mint)
+        .map(|balance| balance.ui_token_amount.decimals)
+}
+
+/// Balance change of a token account. Accounts opened or closed within the
+/// transaction only appear on one side and count as zero on the other.
+pub(crate) fn token_balance_change(token_account: &str, ctx: &DecodeContext) -> Option<TokenAmount> {
+    let pre = find_balance(token_account, ctx.pre_token_balances);
+    let post = find_balance(token_account, ctx.post_token_balances);
+    let decimals = post.or(pre)?.ui_token_amount.decimals;
+    let raw = |balance: Option<&TokenBalance>| match balance {
+        Some(balance) => balance.ui_token_amount.amount.parse::<i128>().ok(),
+        None => Some(0),
+    };
+    Some(TokenAmount {
+        raw: raw(post)? - raw(pre)?,
+        decimals,
+    })
+}
+
+/// Amount moved in or out of a token account by the token transfers the
+/// instruction made itself, and the transfer fees withheld on them (raw).
+/// Transfers made by nested CPIs (the legs under an aggregator route, say)
+/// sit deeper in the stack and are left out.
+///
+/// Token-2022 transfers that don't state their fee are netted against the
+/// account's balance diff: whatever the account got less than it was sent is
+/// taken as withheld. This assumes no other instruction of the transaction
+/// moved the account.
+pub(crate) fn transfer_amount(
+    token_account: &str,
+    ctx: &DecodeContext,
+) -> Option<(TokenAmount, u64)> {
+    let mut transfer = get_token_transfer(token_account, direct_instructions(ctx), ctx.accounts)?;
+    if transfer.unstated_fee_credit > 0 {
+        if let Some(change) = token_balance_change(token_account, ctx) {
+            let withheld = (transfer.amount - change.raw).clamp(0, transfer.unstated_fee_credit);
+            transfer.amount -= withheld;
+            transfer.fee += withheld as u64;
+        }
+    }
+    let decimals = token_decimals(token_account, ctx.post_token_balances)
+        .or_else(|| token_decimals(token_account, ctx.pre_token_balances))?;
+    Some((
+        TokenAmount {
+            raw: transfer.amount,
+            decimals,
         },
-        "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo" => {
-            result = parse_meteora_dlmm_trade_instruction(
-                &instruction_data,
-                input_accounts,
-            );
+        transfer.fee,
+    ))
+}
+
+/// Inner instructions the instruction being decoded invoked itself, leaving
+/// out what those invoked in turn.
+pub(crate) fn direct_instructions<'a>(ctx: &DecodeContext<'a>) -> impl Iterator<Item = &'a InnerInstruction> {
+    // the first nested instruction is always a direct child
+    let height = ctx.inner_instructions.first().map(|inst| inst.stack_height);
+    ctx.inner_instructions
+        .iter()
+        .filter(move |inst| Some(inst.stack_height) == height)
+}
+
+/// Token accounts on the trader's side of the instruction's own transfers
+/// with the given vaults, followed by their owners. Only accounts owned by a
+/// signer of the transaction are kept: program-owned accounts an aggregator
+/// shares between all its users say nothing about who traded.
+pub(crate) fn trader_accounts(vaults: &[&str], ctx: &DecodeContext) -> Vec<String> {
+    let owner_of = |token_account: &str| {
+        find_balance(token_account, ctx.post_token_balances)
+            .or_else(|| find_balance(token_account, ctx.pre_token_balances))
+            .map(|balance| balance.owner.as_str())
+    };
+
+    let mut token_accounts: Vec<String> = vec![];
+    let mut owners: Vec<String> = vec![];
+    for vault in vaults {
+        for account in get_transfer_counterparties(vault, direct_instructions(ctx), ctx.accounts) {
+            let Some(owner) = owner_of(account) else {
+                continue;
+            };
+            if vaults.contains(&account) || !ctx.signers.iter().any(|signer| signer == owner) {
+                continue;
+            }
+            if !token_accounts.iter().any(|known| known == account) {
+                token_accounts.push(account.to_string());
+            }
+            if !owners.iter().any(|known| known == owner) {
+                owners.push(owner.to_string());
+            }
+        }
+    }
+    token_accounts.extend(owners);
+    token_accounts
+}
+
+/// Converts a raw token amount to UI units.
+pub(crate) fn ui_amount(raw: u64, decimals: u32) -> f64 {
+    raw as f64 / 10f64.powi(decimals as i32)
+}
+
+/// Which way a swap leg went, from the signer's side.
+pub(crate) struct TradeDirection<'a> {
+    pub input_mint: &'a str,
+    pub output_mint: &'a str,
+    pub amount_in: TokenAmount,
+    pub amount_out: TokenAmount,
+    /// "Buy" when the signer paid in the pair's quote mint, "Sell" when it
+    /// received it.
+    pub side: &'static str,
+}
+
+/// Reads the direction off the pool deltas: the side the pool received is
+/// the input. The quote mint is whichever of the pair ranks higher in
+/// `QUOTE_PRIORITY`, `quote_mint` when neither is listed. `None` unless the
+/// two sides moved in opposite directions.
+pub(crate) fn trade_direction<'a>(
+    base_mint: &'a str,
+    base_amount: TokenAmount,
+    quote_mint: &'a str,
+    quote_amount: TokenAmount,
+) -> Option<TradeDirection<'a>> {
+    let (input_mint, amount_in, output_mint, amount_out) =
+        if base_amount.raw > 0 && quote_amount.raw < 0 {
+            (base_mint, base_amount, quote_mint, quote_amount)
+        } else if base_amount.raw < 0 && quote_amount.raw > 0 {
+            (quote_mint, quote_amount, base_mint, base_amount)
+        } else {
+            return None;
+        };
+
+    let rank = |mint: &str| QUOTE_PRIORITY.iter().position(|quote| quote == mint);
+    let pair_quote = match (rank(base_mint), rank(quote_mint)) {
+        (Some(base_rank), Some(quote_rank)) if base_rank < quote_rank => base_mint,
+        (Some(_), None) => base_mint,
+        _ => quote_mint,
+    };
+
+    Some(TradeDirection {
+        input_mint,
+        output_mint,
+        amount_in,
+        amount_out: TokenAmount {
+            raw: -amount_out.raw,
+            ..amount_out
         },
-        "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc" => {
-            result = parse_orca_trade_instruction(
-                &instruction_data,
-                &input_accounts,
-            )
+        side: if input_mint == pair_quote { "Buy" } else { "Sell" },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::UiTokenAmount;
+    use crate::utils::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+    use super::*;
+
+    const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const TOKEN_MINT: &str = "4k3Dyjzvzp8eMZWUXbBCjEvwSkkk59S5iCNLY3QrkX6R";
+
+    fn amount(raw: i128, decimals: u32) -> TokenAmount {
+        TokenAmount { raw, decimals }
+    }
+
+    fn balance(address: &str, mint: &str, raw: u64, decimals: u32) -> TokenBalance {
+        TokenBalance {
+            account_index: 0,
+            address: address.to_string(),
+            mint: mint.to_string(),
+            ui_token_amount: UiTokenAmount {
+                ui_amount: ui_amount(raw, decimals),
+                decimals,
+                amount: raw.to_string(),
+                ui_amount_string: String::new(),
+            },
+            owner: String::from("owner"),
+            program_id: String::new(),
+        }
+    }
+
+    /// A token transfer of `amount` from account 2 to account 3, tag first.
+    fn transfer(
+        program_id_index: u32,
+        tag: &[u8],
+        amount: u64,
+        stack_height: u32,
+    ) -> InnerInstruction {
+        let mut data = tag.to_vec();
+        data.extend(amount.to_le_bytes());
+        let accounts = if tag == [3] {
+            vec![2, 3, 4]
+        } else {
+            vec![2, 5, 3, 4]
+        };
+        InnerInstruction {
+            program_id_index,
+            accounts,
+            data,
+            stack_height: Some(stack_height),
         }
-        _ => {}
     }
 
-    result
+    fn accounts() -> Vec<String> {
+        [
+            TOKEN_PROGRAM_ID,
+            TOKEN_2022_PROGRAM_ID,
+            "user",
+            "vault",
+            "authority",
+            TOKEN_MINT,
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    fn context<'a>(
+        accounts: &'a Vec<String>,
+        pre: &'a Vec<TokenBalance>,
+        post: &'a Vec<TokenBalance>,
+        lamports: &'a Vec<u64>,
+        inner_instructions: &'a [InnerInstruction],
+    ) -> DecodeContext<'a> {
+        DecodeContext {
+            data: &[],
+            input_accounts: vec![],
+            accounts,
+            signers: &[],
+            pre_token_balances: pre,
+            post_token_balances: post,
+            pre_balances: lamports,
+            post_balances: lamports,
+            inner_instructions,
+            logs: &[],
+            is_inner: false,
+        }
+    }
+
+    #[test]
+    fn trade_direction_sell_when_pool_receives_base() {
+        let direction =
+            trade_direction(TOKEN_MINT, amount(100, 6), WSOL_MINT, amount(-50, 9)).unwrap();
+        assert_eq!(direction.input_mint, TOKEN_MINT);
+        assert_eq!(direction.output_mint, WSOL_MINT);
+        assert_eq!(direction.amount_in, amount(100, 6));
+        assert_eq!(direction.amount_out, amount(50, 9));
+        assert_eq!(direction.side, "Sell");
+    }
+
+    #[test]
+    fn trade_direction_buy_when_pool_receives_quote() {
+        let direction =
+            trade_direction(TOKEN_MINT, amount(-100, 6), WSOL_MINT, amount(50, 9)).unwrap();
+        assert_eq!(direction.input_mint, WSOL_MINT);
+        assert_eq!(direction.amount_out, amount(100, 6));
+        assert_eq!(direction.side, "Buy");
+    }
+
+    #[test]
+    fn trade_direction_quote_follows_priority_not_position() {
+        // SOL outranks USDC, so paying USDC for SOL sells USDC
+        let direction =
+            trade_direction(WSOL_MINT, amount(-10, 9), USDC_MINT, amount(20, 6)).unwrap();
+        assert_eq!(direction.input_mint, USDC_MINT);
+        assert_eq!(direction.side, "Sell");
+    }
+
+    #[test]
+    fn trade_direction_none_unless_sides_oppose() {
+        assert!(trade_direction(TOKEN_MINT, amount(1, 6), WSOL_MINT, amount(1, 9)).is_none());
+        assert!(trade_direction(TOKEN_MINT, amount(0, 6), WSOL_MINT, amount(-1, 9)).is_none());
+    }
+
+    #[test]
+    fn transfer_amount_skips_nested_transfers() {
+        let accounts = accounts();
+        let pre = vec![balance("vault", TOKEN_MINT, 0, 6)];
+        let post = vec![balance("vault", TOKEN_MINT, 1_000, 6)];
+        let lamports = vec![0; accounts.len()];
+        let inner = [transfer(0, &[3], 1_000, 2), transfer(0, &[3], 400, 3)];
+        let ctx = context(&accounts, &pre, &post, &lamports, &inner);
+
+        assert_eq!(transfer_amount("vault", &ctx), Some((amount(1_000, 6), 0)));
+        assert_eq!(transfer_amount("user", &ctx), None);
+    }
+
+    #[test]
+    fn transfer_amount_nets_unstated_token_2022_fee() {
+        let accounts = accounts();
+        let pre = vec![balance("vault", TOKEN_MINT, 0, 6)];
+        let post = vec![balance("vault", TOKEN_MINT, 990, 6)];
+        let lamports = vec![0; accounts.len()];
+        let mut checked = transfer(1, &[12], 1_000, 2);
+        checked.data.push(6);
+        let inner = [checked];
+        let ctx = context(&accounts, &pre, &post, &lamports, &inner);
+
+        assert_eq!(transfer_amount("vault", &ctx), Some((amount(990, 6), 10)));
+    }
 }
@@ -0,0 +1,203 @@
+use borsh::BorshDeserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::models::{RouteInstruction, TokenAmount, TradeInstruction};
+
+use super::{
+    anchor_discriminator, cpi_events, direct_instructions, mint_decimals, resolve_mint,
+    token_balance_change, DecodeContext, DexDecoder,
+};
+
+pub const JUPITER_PROGRAM_ID: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+
+const ROUTE_DISCRIMINATOR: u64 = u64::from_le_bytes([229, 23, 203, 151, 122, 227, 173, 42]);
+const SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([193, 32, 155, 51, 65, 214, 156, 129]);
+const EXACT_OUT_ROUTE_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([208, 51, 239, 151, 123, 43, 237, 92]);
+
+const SWAP_EVENT_DISCRIMINATOR: u64 = u64::from_le_bytes([64, 198, 205, 232, 38, 8, 113, 226]);
+
+/// Emitted by Jupiter after every leg of a route.
+#[derive(BorshDeserialize)]
+struct SwapEvent {
+    amm: Pubkey,
+    input_mint: Pubkey,
+    input_amount: u64,
+    output_mint: Pubkey,
+    output_amount: u64,
+}
+
+/// Program of the leg a `SwapEvent` reports: the one Jupiter invoked with the
+/// event's AMM among its accounts, or the AMM itself when it is a program.
+fn venue_program(amm: &str, ctx: &DecodeContext) -> Option<String> {
+    direct_instructions(ctx).find_map(|inst| {
+        let program = ctx.accounts.get(inst.program_id_index as usize)?;
+        let invokes_amm = program == amm
+            || inst
+                .accounts
+                .iter()
+                .filter_map(|&idx| ctx.accounts.get(idx as usize))
+                .any(|account| account == amm);
+        (program != JUPITER_PROGRAM_ID && invokes_amm).then(|| program.clone())
+    })
+}
+
+/// Jupiter v6 aggregator routes.
+///
+/// The underlying AMM legs are CPIs decoded by their own venue decoders, so
+/// Jupiter produces no trade rows, only the route as a whole: its first input
+/// and last output, summed over the legs' `SwapEvent`s. Without events the
+/// amounts are the deltas of the user's source and destination token
+/// accounts.
+pub struct JupiterDecoder;
+
+impl DexDecoder for JupiterDecoder {
+    fn program_id(&self) -> &'static str {
+        JUPITER_PROGRAM_ID
+    }
+
+    fn decode(&self, _ctx: &DecodeContext) -> Option<TradeInstruction> {
+        None
+    }
+
+    fn decode_route(&self, ctx: &DecodeContext) -> Option<RouteInstruction> {
+        // (name, source account, destination account, source mint, destination mint) positions
+        let (name, source_idx, destination_idx, source_mint_idx, destination_mint_idx) =
+            match anchor_discriminator(ctx.data)? {
+                ROUTE_DISCRIMINATOR => ("Route", 2, 3, None, Some(5)),
+                SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR => ("SharedAccountsRoute", 3, 6, Some(7), Some(8)),
+                EXACT_OUT_ROUTE_DISCRIMINATOR => ("ExactOutRoute", 2, 3, Some(5), Some(6)),
+                _ => return None,
+            };
+        let source = ctx.input_accounts.get(source_idx)?;
+        let destination = ctx.input_accounts.get(destination_idx)?;
+
+        let events: Vec<SwapEvent> = cpi_events(ctx, JUPITER_PROGRAM_ID, SWAP_EVENT_DISCRIMINATOR);
+        let route = match (events.first(), events.last()) {
+            (Some(first), Some(last)) => {
+                // split routes run several legs off the same mint, so sum them up
+                let input_mint = first.input_mint;
+                let output_mint = last.output_mint;
+                let amount_in: i128 = events
+                    .iter()
+                    .filter(|event| event.input_mint == input_mint)
+                    .map(|event| event.input_amount as i128)
+                    .sum();
+                let amount_out: i128 = events
+                    .iter()
+                    .filter(|event| event.output_mint == output_mint)
+                    .map(|event| event.output_amount as i128)
+                    .sum();
+                let mut venues: Vec<String> = vec![];
+                for event in events.iter() {
+                    let Some(venue) = venue_program(&event.amm.to_string(), ctx) else {
+                        continue;
+                    };
+                    if !venues.contains(&venue) {
+                        venues.push(venue);
+                    }
+                }
+
+                let input_mint = input_mint.to_string();
+                let output_mint = output_mint.to_string();
+                RouteInstruction {
+                    amount_in: mint_decimals(&input_mint, ctx).map(|decimals| TokenAmount {
+                        raw: amount_in,
+                        decimals,
+                    }),
+                    amount_out: mint_decimals(&output_mint, ctx).map(|decimals| TokenAmount {
+                        raw: amount_out,
+                        decimals,
+                    }),
+                    input_mint,
+                    output_mint,
+                    hop_count: events.len() as u32,
+                    venues,
+                    ..Default::default()
+                }
+            }
+            _ => {
+                let account_at =
+                    |idx: Option<usize>| idx.and_then(|idx| ctx.input_accounts.get(idx)).cloned();
+                let amount_in = token_balance_change(source, ctx);
+                RouteInstruction {
+                    // the source may be a mint-less wrapped SOL account closed by the route
+                    input_mint: account_at(source_mint_idx)
+                        .or_else(|| resolve_mint("", source, ctx))
+                        .unwrap_or_default(),
+                    output_mint: account_at(destination_mint_idx)
+                        .or_else(|| resolve_mint("", destination, ctx))
+                        .unwrap_or_default(),
+                    // the user's source account paid in, so its delta is negative
+                    amount_in: amount_in.map(|amount| TokenAmount {
+                        raw: -amount.raw,
+                        ..amount
+                    }),
+                    amount_out: token_balance_change(destination, ctx),
+                    ..Default::default()
+                }
+            }
+        };
+
+        Some(RouteInstruction {
+            dapp_address: String::from(JUPITER_PROGRAM_ID),
+            name: String::from(name),
+            ..route
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::InnerInstruction;
+
+    use super::*;
+
+    const WHIRLPOOL_PROGRAM_ID: &str = "whLbMiqkXTBQGkqMTYjYZnpnT1EkRXcTrP4ksovRQcU";
+
+    fn invoke(program_id_index: u32, accounts: Vec<u8>) -> InnerInstruction {
+        InnerInstruction {
+            program_id_index,
+            accounts,
+            data: vec![],
+            stack_height: Some(2),
+        }
+    }
+
+    #[test]
+    fn venues_are_the_programs_of_the_legs() {
+        let accounts: Vec<String> = [
+            JUPITER_PROGRAM_ID,
+            WHIRLPOOL_PROGRAM_ID,
+            "pool",
+            "authority",
+        ]
+        .map(String::from)
+        .to_vec();
+        let inner_instructions = [invoke(0, vec![3]), invoke(1, vec![2])];
+        let ctx = DecodeContext {
+            data: &[],
+            input_accounts: vec![],
+            accounts: &accounts,
+            signers: &[],
+            pre_token_balances: &vec![],
+            post_token_balances: &vec![],
+            pre_balances: &vec![],
+            post_balances: &vec![],
+            inner_instructions: &inner_instructions,
+            logs: &[],
+            is_inner: false,
+        };
+
+        assert_eq!(
+            venue_program("pool", &ctx),
+            Some(String::from(WHIRLPOOL_PROGRAM_ID))
+        );
+        assert_eq!(
+            venue_program(WHIRLPOOL_PROGRAM_ID, &ctx),
+            Some(String::from(WHIRLPOOL_PROGRAM_ID))
+        );
+        assert_eq!(venue_program("authority", &ctx), None);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::models::{
+    LiquidityAction, LiquidityInstruction, PoolCreationInstruction, TokenAmount, TradeInstruction,
+};
+
+use super::{anchor_discriminator, token_balance_change, DecodeContext, DexDecoder};
+
+pub const METEORA_PROGRAM_ID: &str = "Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB";
+
+const SWAP_DISCRIMINATOR: u64 = u64::from_le_bytes([248, 198, 158, 145, 225, 117, 135, 200]);
+const ADD_BALANCE_LIQUIDITY_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([168, 227, 50, 62, 189, 171, 84, 176]);
+const ADD_IMBALANCE_LIQUIDITY_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([79, 35, 122, 84, 173, 15, 93, 191]);
+const BOOTSTRAP_LIQUIDITY_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([4, 228, 215, 71, 225, 253, 119, 206]);
+const REMOVE_BALANCE_LIQUIDITY_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([133, 109, 44, 179, 56, 238, 114, 33]);
+const REMOVE_LIQUIDITY_SINGLE_SIDE_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([84, 84, 177, 66, 254, 185, 10, 251]);
+const INITIALIZE_PERMISSIONLESS_POOL_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([118, 173, 41, 157, 173, 72, 97, 103]);
+const INITIALIZE_PERMISSIONLESS_POOL_WITH_FEE_TIER_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([6, 135, 68, 147, 229, 82, 169, 113]);
+
+/// Meteora dynamic AMM pools.
+///
+/// Deposits and withdrawals pass the LP mint at 1, the provider's LP account
+/// at 2 and the token vaults at 9/10. Tokens move through the vault program,
+/// so their amounts come from the vault balance diff.
+///
+/// Permissionless pool creation passes the mints at 2/3, the token vaults at
+/// 6/7 and the payer at 17; the initial deposit is read the same way.
+pub struct MeteoraDecoder;
+impl DexDecoder for MeteoraDecoder {
+    fn program_id(&self) -> &'static str {
+        METEORA_PROGRAM_ID
+    }
+
+    fn decode(&self, ctx: &DecodeContext) -> Option<TradeInstruction> {
+        match anchor_discriminator(ctx.data)? {
+            SWAP_DISCRIMINATOR => Some(TradeInstruction {
+                dapp_address: String::from(METEORA_PROGRAM_ID),
+                dex: String::from("METEORA"),
+                name: String::from("Swap"),
+                amm: ctx.input_accounts.first()?.to_string(),
+                vault_a: ctx.input_accounts.get(5)?.to_string(),
+                vault_b: ctx.input_accounts.get(6)?.to_string(),
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    }
+
+    fn decode_liquidity(&self, ctx: &DecodeContext) -> Option<LiquidityInstruction> {
+        let (name, action) = match anchor_discriminator(ctx.data)? {
+            ADD_BALANCE_LIQUIDITY_DISCRIMINATOR => ("AddBalanceLiquidity", LiquidityAction::Add),
+            ADD_IMBALANCE_LIQUIDITY_DISCRIMINATOR => {
+                ("AddImbalanceLiquidity", LiquidityAction::Add)
+            }
+            BOOTSTRAP_LIQUIDITY_DISCRIMINATOR => ("BootstrapLiquidity", LiquidityAction::Add),
+            REMOVE_BALANCE_LIQUIDITY_DISCRIMINATOR => {
+                ("RemoveBalanceLiquidity", LiquidityAction::Remove)
+            }
+            REMOVE_LIQUIDITY_SINGLE_SIDE_DISCRIMINATOR => {
+                ("RemoveLiquiditySingleSide", LiquidityAction::Remove)
+            }
+            _ => return None,
+        };
+
+        let user_pool_lp = ctx.input_accounts.get(2)?;
+        Some(LiquidityInstruction {
+            dapp_address: String::from(METEORA_PROGRAM_ID),
+            name: String::from(name),
+            action,
+            amm: ctx.input_accounts.first()?.to_string(),
+            lp_mint: ctx.input_accounts.get(1)?.to_string(),
+            vault_a: ctx.input_accounts.get(9)?.to_string(),
+            vault_b: ctx.input_accounts.get(10)?.to_string(),
+            lp_amount: token_balance_change(user_pool_lp, ctx).map(|amount| TokenAmount {
+                raw: amount.raw.abs(),
+                ..amount
+            }),
+            ..Default::default()
+        })
+    }
+
+    fn decode_pool_creation(&self, ctx: &DecodeContext) -> Option<PoolCreationInstruction> {
+        let name = match anchor_discriminator(ctx.data)? {
+            INITIALIZE_PERMISSIONLESS_POOL_DISCRIMINATOR => "InitializePermissionlessPool",
+            INITIALIZE_PERMISSIONLESS_POOL_WITH_FEE_TIER_DISCRIMINATOR => {
+                "InitializePermissionlessPoolWithFeeTier"
+            }
+            _ => return None,
+        };
+
+        Some(PoolCreationInstruction {
+            dapp_address: String::from(METEORA_PROGRAM_ID),
+            name: String::from(name),
+            amm: ctx.input_accounts.first()?.to_string(),
+            creator: ctx.input_accounts.get(17)?.to_string(),
+            lp_mint: ctx.input_accounts.get(1)?.to_string(),
+            mint_a: ctx.input_accounts.get(2)?.to_string(),
+            mint_b: ctx.input_accounts.get(3)?.to_string(),
+            vault_a: ctx.input_accounts.get(6)?.to_string(),
+            vault_b: ctx.input_accounts.get(7)?.to_string(),
+            ..Default::default()
+        })
+    }
+}
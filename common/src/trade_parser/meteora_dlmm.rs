@@ -0,0 +1,252 @@
+use borsh::BorshDeserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::models::{
+    LiquidityAction, LiquidityInstruction, PoolCreationInstruction, TokenAmount, TradeInstruction,
+};
+
+use super::{
+    anchor_discriminator, cpi_events, mint_decimals, token_decimals, ui_amount, DecodeContext,
+    DexDecoder,
+};
+
+pub const METEORA_DLMM_PROGRAM_ID: &str = "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo";
+
+const SWAP_DISCRIMINATOR: u64 = u64::from_le_bytes([248, 198, 158, 145, 225, 117, 135, 200]);
+const SWAP_EXACT_OUT_DISCRIMINATOR: u64 = u64::from_le_bytes([250, 73, 101, 33, 38, 207, 75, 184]);
+const SWAP_WITH_PRICE_IMPACT_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([56, 173, 230, 208, 173, 228, 156, 205]);
+
+const ADD_LIQUIDITY_DISCRIMINATOR: u64 = u64::from_le_bytes([181, 157, 89, 67, 143, 182, 52, 72]);
+const ADD_LIQUIDITY_BY_WEIGHT_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([28, 140, 238, 99, 231, 162, 21, 149]);
+const ADD_LIQUIDITY_BY_STRATEGY_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([7, 3, 150, 127, 148, 40, 61, 200]);
+const ADD_LIQUIDITY_BY_STRATEGY_ONE_SIDE_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([41, 5, 238, 175, 100, 225, 6, 205]);
+const ADD_LIQUIDITY_ONE_SIDE_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([94, 155, 103, 151, 70, 95, 220, 165]);
+const ADD_LIQUIDITY_ONE_SIDE_PRECISE_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([161, 194, 103, 84, 171, 71, 250, 154]);
+const REMOVE_LIQUIDITY_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([80, 85, 209, 72, 24, 206, 177, 108]);
+const REMOVE_LIQUIDITY_BY_RANGE_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([26, 82, 102, 152, 240, 74, 105, 26]);
+const REMOVE_ALL_LIQUIDITY_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([10, 51, 61, 35, 112, 105, 24, 85]);
+const INITIALIZE_LB_PAIR_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([45, 154, 237, 210, 221, 15, 166, 92]);
+
+const SWAP_EVENT_DISCRIMINATOR: u64 = u64::from_le_bytes([81, 108, 227, 190, 205, 208, 10, 196]);
+const ADD_LIQUIDITY_EVENT_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([31, 94, 125, 90, 227, 52, 61, 186]);
+const REMOVE_LIQUIDITY_EVENT_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([116, 244, 97, 232, 103, 31, 152, 58]);
+
+/// Leading fields of the DLMM `Swap` event; later fields are ignored.
+#[derive(BorshDeserialize)]
+struct Swap {
+    lb_pair: Pubkey,
+    _from: Pubkey,
+    _start_bin_id: i32,
+    _end_bin_id: i32,
+    amount_in: u64,
+    amount_out: u64,
+    swap_for_y: bool,
+    fee: u64,
+}
+
+/// Layout shared by the DLMM `AddLiquidity` and `RemoveLiquidity` events.
+#[derive(BorshDeserialize)]
+struct LiquidityChange {
+    lb_pair: Pubkey,
+    _from: Pubkey,
+    position: Pubkey,
+    /// Token x and token y moved, raw.
+    amounts: [u64; 2],
+    _active_bin_id: i32,
+}
+
+/// Meteora DLMM (liquidity book) pairs.
+///
+/// Amounts and fees come from the pair's `Swap` event when one was emitted.
+/// The event only carries the active bin id, not the bin step, so there's no
+/// post-swap price. Liquidity changes likewise read their amounts from the
+/// `AddLiquidity`/`RemoveLiquidity` events.
+///
+/// New pairs open empty, at the price of the active bin passed to
+/// `initialize_lb_pair`.
+pub struct MeteoraDlmmDecoder;
+
+impl DexDecoder for MeteoraDlmmDecoder {
+    fn program_id(&self) -> &'static str {
+        METEORA_DLMM_PROGRAM_ID
+    }
+
+    fn decode(&self, ctx: &DecodeContext) -> Option<TradeInstruction> {
+        let name = match anchor_discriminator(ctx.data)? {
+            SWAP_DISCRIMINATOR => "Swap",
+            SWAP_EXACT_OUT_DISCRIMINATOR => "SwapExactOut",
+            SWAP_WITH_PRICE_IMPACT_DISCRIMINATOR => "SwapWithPriceImpact",
+            _ => return None,
+        };
+
+        let lb_pair = ctx.input_accounts.first()?;
+        let reserve_x = ctx.input_accounts.get(2)?;
+        let reserve_y = ctx.input_accounts.get(3)?;
+
+        let event = cpi_events::<Swap>(ctx, METEORA_DLMM_PROGRAM_ID, SWAP_EVENT_DISCRIMINATOR)
+            .into_iter()
+            .find(|event| event.lb_pair.to_string() == *lb_pair);
+        let decimals_x = token_decimals(reserve_x, ctx.post_token_balances);
+        let decimals_y = token_decimals(reserve_y, ctx.post_token_balances);
+        let (amount_a, amount_b, fee) = match (event, decimals_x, decimals_y) {
+            (Some(event), Some(decimals_x), Some(decimals_y)) => {
+                let (decimals_in, decimals_out) = if event.swap_for_y {
+                    (decimals_x, decimals_y)
+                } else {
+                    (decimals_y, decimals_x)
+                };
+                let amount_in = TokenAmount {
+                    raw: event.amount_in as i128,
+                    decimals: decimals_in,
+                };
+                let amount_out = TokenAmount {
+                    raw: -(event.amount_out as i128),
+                    decimals: decimals_out,
+                };
+                let fee = ui_amount(event.fee, decimals_in);
+                if event.swap_for_y {
+                    (Some(amount_in), Some(amount_out), Some(fee))
+                } else {
+                    (Some(amount_out), Some(amount_in), Some(fee))
+                }
+            }
+            _ => (None, None, None),
+        };
+
+        Some(TradeInstruction {
+            dapp_address: String::from(METEORA_DLMM_PROGRAM_ID),
+            dex: String::from("METEORA"),
+            name: String::from(name),
+            amm: lb_pair.to_string(),
+            vault_a: reserve_x.to_string(),
+            vault_b: reserve_y.to_string(),
+            amount_a,
+            amount_b,
+            fee,
+            ..Default::default()
+        })
+    }
+
+    fn decode_liquidity(&self, ctx: &DecodeContext) -> Option<LiquidityInstruction> {
+        use LiquidityAction::{Add, Remove};
+        let (name, action, one_sided) = match anchor_discriminator(ctx.data)? {
+            ADD_LIQUIDITY_DISCRIMINATOR => ("AddLiquidity", Add, false),
+            ADD_LIQUIDITY_BY_WEIGHT_DISCRIMINATOR => ("AddLiquidityByWeight", Add, false),
+            ADD_LIQUIDITY_BY_STRATEGY_DISCRIMINATOR => ("AddLiquidityByStrategy", Add, false),
+            ADD_LIQUIDITY_BY_STRATEGY_ONE_SIDE_DISCRIMINATOR => {
+                ("AddLiquidityByStrategyOneSide", Add, true)
+            }
+            ADD_LIQUIDITY_ONE_SIDE_DISCRIMINATOR => ("AddLiquidityOneSide", Add, true),
+            ADD_LIQUIDITY_ONE_SIDE_PRECISE_DISCRIMINATOR => {
+                ("AddLiquidityOneSidePrecise", Add, true)
+            }
+            REMOVE_LIQUIDITY_DISCRIMINATOR => ("RemoveLiquidity", Remove, false),
+            REMOVE_LIQUIDITY_BY_RANGE_DISCRIMINATOR => ("RemoveLiquidityByRange", Remove, false),
+            REMOVE_ALL_LIQUIDITY_DISCRIMINATOR => ("RemoveAllLiquidity", Remove, false),
+            _ => return None,
+        };
+
+        let position = ctx.input_accounts.first()?;
+        let lb_pair = ctx.input_accounts.get(1)?;
+        // one-sided deposits pass the reserve they pay into and its mint at
+        // 4/5, everything else both reserves at 5/6 and both mints at 7/8
+        let account_at = |idx: usize| ctx.input_accounts.get(idx).cloned();
+        let (vault_a, vault_b, mint_a, mint_b) = if one_sided {
+            (account_at(4)?, String::new(), account_at(5)?, String::new())
+        } else {
+            (account_at(5)?, account_at(6)?, account_at(7)?, account_at(8)?)
+        };
+
+        let (event_discriminator, sign) = match action {
+            Add => (ADD_LIQUIDITY_EVENT_DISCRIMINATOR, 1),
+            Remove => (REMOVE_LIQUIDITY_EVENT_DISCRIMINATOR, -1),
+        };
+        let event = cpi_events::<LiquidityChange>(ctx, METEORA_DLMM_PROGRAM_ID, event_discriminator)
+            .into_iter()
+            .find(|event| {
+                event.lb_pair.to_string() == *lb_pair && event.position.to_string() == *position
+            });
+        let decimals = |vault: &str, mint: &str| {
+            token_decimals(vault, ctx.post_token_balances).or_else(|| mint_decimals(mint, ctx))
+        };
+        let amount = |raw: u64, decimals: Option<u32>| {
+            decimals.map(|decimals| TokenAmount {
+                raw: sign * raw as i128,
+                decimals,
+            })
+        };
+        let (amount_a, amount_b) = match event {
+            // the side that wasn't deposited is 0
+            Some(event) if one_sided => (
+                amount(
+                    event.amounts[0].saturating_add(event.amounts[1]),
+                    decimals(&vault_a, &mint_a),
+                ),
+                None,
+            ),
+            Some(event) => (
+                amount(event.amounts[0], decimals(&vault_a, &mint_a)),
+                amount(event.amounts[1], decimals(&vault_b, &mint_b)),
+            ),
+            None => (None, None),
+        };
+
+        Some(LiquidityInstruction {
+            dapp_address: String::from(METEORA_DLMM_PROGRAM_ID),
+            name: String::from(name),
+            action,
+            amm: lb_pair.to_string(),
+            position: position.to_string(),
+            vault_a,
+            vault_b,
+            mint_a,
+            mint_b,
+            amount_a,
+            amount_b,
+            ..Default::default()
+        })
+    }
+
+    fn decode_pool_creation(&self, ctx: &DecodeContext) -> Option<PoolCreationInstruction> {
+        if anchor_discriminator(ctx.data)? != INITIALIZE_LB_PAIR_DISCRIMINATOR {
+            return None;
+        }
+        let active_id = i32::from_le_bytes(ctx.data.get(8..12)?.try_into().ok()?);
+        let bin_step = u16::from_le_bytes(ctx.data.get(12..14)?.try_into().ok()?);
+
+        let mint_x = ctx.input_accounts.get(2)?;
+        let mint_y = ctx.input_accounts.get(3)?;
+        // a bin's raw price is (1 + bin_step / 10000) ^ bin_id, y per x
+        let initial_price = mint_decimals(mint_x, ctx)
+            .zip(mint_decimals(mint_y, ctx))
+            .map(|(decimals_x, decimals_y)| {
+                (1.0 + bin_step as f64 / 10_000.0).powi(active_id)
+                    * 10f64.powi(decimals_x as i32 - decimals_y as i32)
+            });
+
+        Some(PoolCreationInstruction {
+            dapp_address: String::from(METEORA_DLMM_PROGRAM_ID),
+            name: String::from("InitializeLbPair"),
+            amm: ctx.input_accounts.first()?.to_string(),
+            creator: ctx.input_accounts.get(8)?.to_string(),
+            mint_a: mint_x.to_string(),
+            mint_b: mint_y.to_string(),
+            vault_a: ctx.input_accounts.get(4)?.to_string(),
+            vault_b: ctx.input_accounts.get(5)?.to_string(),
+            initial_price,
+            ..Default::default()
+        })
+    }
+}
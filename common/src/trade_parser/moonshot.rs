@@ -0,0 +1,56 @@
+use crate::models::{TokenAmount, TradeInstruction};
+use crate::utils::get_lamports_change;
+
+use super::{anchor_discriminator, DecodeContext, DexDecoder, SOL_DECIMALS, WSOL_MINT};
+
+pub const MOONSHOT_PROGRAM_ID: &str = "MoonCVVNZFSYkqNXP6bxHLPL6QQJiMagDL3qcqUQTrG";
+
+const BUY_DISCRIMINATOR: u64 = u64::from_le_bytes([102, 6, 61, 18, 1, 218, 235, 234]);
+const SELL_DISCRIMINATOR: u64 = u64::from_le_bytes([51, 230, 133, 164, 1, 127, 131, 173]);
+
+/// Moonshot bonding curves.
+///
+/// Like Pump.fun the curve account holds the SOL side as lamports and the
+/// curve token account holds the token side. SOL amounts are the curve's
+/// lamport delta, token amounts the token account's balance diff.
+pub struct MoonshotDecoder;
+
+impl DexDecoder for MoonshotDecoder {
+    fn program_id(&self) -> &'static str {
+        MOONSHOT_PROGRAM_ID
+    }
+
+    fn decode(&self, ctx: &DecodeContext) -> Option<TradeInstruction> {
+        let name = match anchor_discriminator(ctx.data)? {
+            BUY_DISCRIMINATOR => "Buy",
+            SELL_DISCRIMINATOR => "Sell",
+            _ => return None,
+        };
+        let curve_account = ctx.input_accounts.get(2)?;
+        let curve_token_account = ctx.input_accounts.get(3)?;
+        let mint = ctx.input_accounts.get(6)?;
+
+        let lamports = get_lamports_change(
+            curve_account,
+            ctx.accounts,
+            ctx.pre_balances,
+            ctx.post_balances,
+        )?;
+
+        Some(TradeInstruction {
+            dapp_address: String::from(MOONSHOT_PROGRAM_ID),
+            dex: String::from("MOONSHOT"),
+            name: String::from(name),
+            amm: curve_account.to_string(),
+            vault_a: curve_token_account.to_string(),
+            vault_b: curve_account.to_string(),
+            mint_a: mint.to_string(),
+            mint_b: String::from(WSOL_MINT),
+            amount_b: Some(TokenAmount {
+                raw: lamports,
+                decimals: SOL_DECIMALS,
+            }),
+            ..Default::default()
+        })
+    }
+}
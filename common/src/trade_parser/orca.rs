@@ -0,0 +1,275 @@
+use borsh::BorshDeserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::models::{
+    LiquidityAction, LiquidityInstruction, PoolCreationInstruction, TokenAmount, TradeInstruction,
+};
+
+use super::{
+    anchor_discriminator, cpi_events, log_events, mint_decimals, token_decimals, ui_amount,
+    DecodeContext, DexDecoder,
+};
+
+pub const ORCA_PROGRAM_ID: &str = "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc";
+
+const SWAP_DISCRIMINATOR: u64 = 14449647541112719096;
+const SWAP_V2_DISCRIMINATOR: u64 = 7070309578724672555;
+const TWO_HOP_SWAP_DISCRIMINATOR: u64 = 16635068063392030915;
+const TWO_HOP_SWAP_V2_DISCRIMINATOR: u64 = 8485347938364657594;
+const INCREASE_LIQUIDITY_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([46, 156, 243, 118, 13, 205, 251, 178]);
+const INCREASE_LIQUIDITY_V2_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([133, 29, 89, 223, 69, 238, 176, 10]);
+const DECREASE_LIQUIDITY_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([160, 38, 208, 111, 104, 91, 44, 1]);
+const DECREASE_LIQUIDITY_V2_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([58, 127, 188, 62, 79, 82, 196, 96]);
+const INITIALIZE_POOL_DISCRIMINATOR: u64 = u64::from_le_bytes([95, 180, 10, 172, 84, 174, 232, 40]);
+const INITIALIZE_POOL_V2_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([207, 45, 87, 242, 27, 63, 204, 67]);
+
+const TRADED_EVENT_DISCRIMINATOR: u64 = u64::from_le_bytes([225, 202, 73, 175, 147, 43, 160, 150]);
+
+/// Emitted by the whirlpool program for every pool a swap goes through.
+#[derive(BorshDeserialize)]
+struct Traded {
+    whirlpool: Pubkey,
+    a_to_b: bool,
+    _pre_sqrt_price: u128,
+    post_sqrt_price: u128,
+    input_amount: u64,
+    output_amount: u64,
+    input_transfer_fee: u64,
+    output_transfer_fee: u64,
+    lp_fee: u64,
+    protocol_fee: u64,
+}
+
+/// Orca Whirlpools.
+///
+/// Two-hop swaps go through two whirlpools in one instruction and produce a
+/// leg per whirlpool. Each leg takes its exact amounts, fee and post-swap
+/// price from the whirlpool's `Traded` event when one was emitted.
+///
+/// Liquidity changes on positions take their amounts from the vault
+/// transfers; the liquidity delta is the first argument of the instruction.
+///
+/// Whirlpools open empty, at the sqrt price passed to `initialize_pool`.
+pub struct OrcaDecoder;
+
+impl DexDecoder for OrcaDecoder {
+    fn program_id(&self) -> &'static str {
+        ORCA_PROGRAM_ID
+    }
+
+    fn decode(&self, ctx: &DecodeContext) -> Option<TradeInstruction> {
+        self.decode_legs(ctx).into_iter().next()
+    }
+
+    fn decode_legs(&self, ctx: &DecodeContext) -> Vec<TradeInstruction> {
+        let discriminator = match anchor_discriminator(ctx.data) {
+            Some(discriminator) => discriminator,
+            None => return vec![],
+        };
+
+        // (whirlpool, vault a, vault b) account positions of each hop
+        let (name, hops): (&str, &[(usize, usize, usize)]) = match discriminator {
+            SWAP_DISCRIMINATOR => ("Swap", &[(2, 4, 6)]),
+            SWAP_V2_DISCRIMINATOR => ("SwapV2", &[(4, 8, 10)]),
+            TWO_HOP_SWAP_DISCRIMINATOR => ("TwoHopSwap", &[(2, 5, 7), (3, 9, 11)]),
+            TWO_HOP_SWAP_V2_DISCRIMINATOR => ("TwoHopSwapV2", &[(0, 9, 10), (1, 11, 12)]),
+            _ => return vec![],
+        };
+
+        // v2 two-hops pass the input, intermediate and output mints up front,
+        // the vaults are ordered input -> intermediate -> output
+        let mints: Vec<(String, String)> = match discriminator {
+            TWO_HOP_SWAP_V2_DISCRIMINATOR => {
+                let mint_at = |idx: usize| ctx.input_accounts.get(idx).cloned().unwrap_or_default();
+                vec![(mint_at(2), mint_at(3)), (mint_at(3), mint_at(4))]
+            }
+            _ => vec![],
+        };
+
+        // `Traded` is logged with `emit!`; older or forked deployments may
+        // emit it through a self-CPI instead
+        let mut events: Vec<Traded> = log_events(ctx, TRADED_EVENT_DISCRIMINATOR);
+        if events.is_empty() {
+            events = cpi_events(ctx, ORCA_PROGRAM_ID, TRADED_EVENT_DISCRIMINATOR);
+        }
+
+        hops.iter()
+            .enumerate()
+            .map(|(hop, &(amm_idx, vault_a_idx, vault_b_idx))| {
+                let (mint_a, mint_b) = mints.get(hop).cloned().unwrap_or_default();
+                let mut trade = TradeInstruction {
+                    dapp_address: String::from(ORCA_PROGRAM_ID),
+                    dex: String::from("ORCA"),
+                    name: String::from(name),
+                    amm: ctx.input_accounts.get(amm_idx)?.to_string(),
+                    vault_a: ctx.input_accounts.get(vault_a_idx)?.to_string(),
+                    vault_b: ctx.input_accounts.get(vault_b_idx)?.to_string(),
+                    mint_a,
+                    mint_b,
+                    ..Default::default()
+                };
+                let event = events
+                    .iter()
+                    .find(|event| event.whirlpool.to_string() == trade.amm);
+                if let Some(event) = event {
+                    // v2 two-hop vaults are already in input -> output order
+                    let ordered_by_direction = discriminator == TWO_HOP_SWAP_V2_DISCRIMINATOR;
+                    apply_traded_event(&mut trade, event, ordered_by_direction, ctx);
+                }
+                Some(trade)
+            })
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default()
+    }
+
+    fn decode_liquidity(&self, ctx: &DecodeContext) -> Option<LiquidityInstruction> {
+        // (position, v2 mints a/b, vault a, vault b) account positions
+        let (name, action, accounts) = match anchor_discriminator(ctx.data)? {
+            INCREASE_LIQUIDITY_DISCRIMINATOR => {
+                ("IncreaseLiquidity", LiquidityAction::Add, (3, None, 7, 8))
+            }
+            INCREASE_LIQUIDITY_V2_DISCRIMINATOR => {
+                ("IncreaseLiquidityV2", LiquidityAction::Add, (5, Some((7, 8)), 11, 12))
+            }
+            DECREASE_LIQUIDITY_DISCRIMINATOR => {
+                ("DecreaseLiquidity", LiquidityAction::Remove, (3, None, 7, 8))
+            }
+            DECREASE_LIQUIDITY_V2_DISCRIMINATOR => {
+                ("DecreaseLiquidityV2", LiquidityAction::Remove, (5, Some((7, 8)), 11, 12))
+            }
+            _ => return None,
+        };
+        let (position_idx, mint_idx, vault_a_idx, vault_b_idx) = accounts;
+        let (mint_a, mint_b) = match mint_idx {
+            Some((mint_a_idx, mint_b_idx)) => (
+                ctx.input_accounts.get(mint_a_idx)?.to_string(),
+                ctx.input_accounts.get(mint_b_idx)?.to_string(),
+            ),
+            None => Default::default(),
+        };
+        let liquidity = ctx
+            .data
+            .get(8..24)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u128::from_le_bytes);
+
+        Some(LiquidityInstruction {
+            dapp_address: String::from(ORCA_PROGRAM_ID),
+            name: String::from(name),
+            action,
+            amm: ctx.input_accounts.first()?.to_string(),
+            position: ctx.input_accounts.get(position_idx)?.to_string(),
+            vault_a: ctx.input_accounts.get(vault_a_idx)?.to_string(),
+            vault_b: ctx.input_accounts.get(vault_b_idx)?.to_string(),
+            mint_a,
+            mint_b,
+            liquidity,
+            ..Default::default()
+        })
+    }
+
+    fn decode_pool_creation(&self, ctx: &DecodeContext) -> Option<PoolCreationInstruction> {
+        // (funder, whirlpool, vault a, vault b, sqrt price offset) positions;
+        // v1 has a bump byte before the tick spacing
+        let (name, accounts) = match anchor_discriminator(ctx.data)? {
+            INITIALIZE_POOL_DISCRIMINATOR => ("InitializePool", (3, 4, 5, 6, 11)),
+            INITIALIZE_POOL_V2_DISCRIMINATOR => ("InitializePoolV2", (5, 6, 7, 8, 10)),
+            _ => return None,
+        };
+        let (funder_idx, whirlpool_idx, vault_a_idx, vault_b_idx, price_offset) = accounts;
+        let mint_a = ctx.input_accounts.get(1)?;
+        let mint_b = ctx.input_accounts.get(2)?;
+
+        // Q64.64 sqrt of the raw price, b per a
+        let initial_price = ctx
+            .data
+            .get(price_offset..price_offset + 16)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u128::from_le_bytes)
+            .zip(mint_decimals(mint_a, ctx).zip(mint_decimals(mint_b, ctx)))
+            .map(|(sqrt_price, (decimals_a, decimals_b))| {
+                (sqrt_price as f64 / 2f64.powi(64)).powi(2)
+                    * 10f64.powi(decimals_a as i32 - decimals_b as i32)
+            });
+
+        Some(PoolCreationInstruction {
+            dapp_address: String::from(ORCA_PROGRAM_ID),
+            name: String::from(name),
+            amm: ctx.input_accounts.get(whirlpool_idx)?.to_string(),
+            creator: ctx.input_accounts.get(funder_idx)?.to_string(),
+            mint_a: mint_a.to_string(),
+            mint_b: mint_b.to_string(),
+            vault_a: ctx.input_accounts.get(vault_a_idx)?.to_string(),
+            vault_b: ctx.input_accounts.get(vault_b_idx)?.to_string(),
+            initial_price,
+            ..Default::default()
+        })
+    }
+}
+
+/// Fills in the leg's amounts, fees and post-swap price from its `Traded`
+/// event. Legs whose vault decimals can't be found are left as they are.
+fn apply_traded_event(
+    trade: &mut TradeInstruction,
+    event: &Traded,
+    ordered_by_direction: bool,
+    ctx: &DecodeContext,
+) {
+    let decimals_a = token_decimals(&trade.vault_a, ctx.post_token_balances);
+    let decimals_b = token_decimals(&trade.vault_b, ctx.post_token_balances);
+    let (Some(decimals_a), Some(decimals_b)) = (decimals_a, decimals_b) else {
+        return;
+    };
+
+    // whether the pool received the input in vault_a
+    let input_in_a = ordered_by_direction || event.a_to_b;
+    let (decimals_in, decimals_out) = if input_in_a {
+        (decimals_a, decimals_b)
+    } else {
+        (decimals_b, decimals_a)
+    };
+    // the input amount includes the transfer fee the vault never receives
+    let amount_in = TokenAmount {
+        raw: event.input_amount.saturating_sub(event.input_transfer_fee) as i128,
+        decimals: decimals_in,
+    };
+    // and the output is what reaches the user, net of its transfer fee
+    let amount_out = TokenAmount {
+        raw: -(event.output_amount.saturating_sub(event.output_transfer_fee) as i128),
+        decimals: decimals_out,
+    };
+    let (amount_a, amount_b, transfer_fee_a, transfer_fee_b) = if input_in_a {
+        (amount_in, amount_out, event.input_transfer_fee, event.output_transfer_fee)
+    } else {
+        (amount_out, amount_in, event.output_transfer_fee, event.input_transfer_fee)
+    };
+
+    // sqrt_price is Q64.64 of raw token B per raw token A, vault_a is only
+    // token A when the vaults follow the pool's order
+    let vault_a_is_token_a = input_in_a == event.a_to_b;
+    let (decimals_token_a, decimals_token_b) = if vault_a_is_token_a {
+        (decimals_a, decimals_b)
+    } else {
+        (decimals_b, decimals_a)
+    };
+    let sqrt_price = event.post_sqrt_price as f64 / 2f64.powi(64);
+    let price_b_per_a =
+        sqrt_price * sqrt_price * 10f64.powi(decimals_token_a as i32 - decimals_token_b as i32);
+    let post_price = if vault_a_is_token_a {
+        price_b_per_a
+    } else {
+        1.0 / price_b_per_a
+    };
+
+    trade.amount_a = Some(amount_a);
+    trade.amount_b = Some(amount_b);
+    trade.transfer_fee_a = Some(transfer_fee_a);
+    trade.transfer_fee_b = Some(transfer_fee_b);
+    trade.fee = Some(ui_amount(event.lp_fee.saturating_add(event.protocol_fee), decimals_in));
+    trade.post_price = Some(post_price);
+}
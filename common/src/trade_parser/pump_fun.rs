@@ -0,0 +1,212 @@
+use borsh::BorshDeserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::models::{MigrationInstruction, TokenAmount, TradeInstruction};
+use crate::utils::get_lamports_change;
+
+use super::{
+    anchor_discriminator, cpi_events, find_balance, log_events, token_balance_change,
+    token_decimals, DecodeContext, DexDecoder, SOL_DECIMALS, WSOL_MINT,
+};
+
+pub const PUMP_FUN_PROGRAM_ID: &str = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
+
+const BUY_DISCRIMINATOR: u64 = u64::from_le_bytes([102, 6, 61, 18, 1, 218, 235, 234]);
+const SELL_DISCRIMINATOR: u64 = u64::from_le_bytes([51, 230, 133, 164, 1, 127, 131, 173]);
+const WITHDRAW_DISCRIMINATOR: u64 = u64::from_le_bytes([183, 18, 70, 156, 148, 109, 161, 34]);
+const MIGRATE_DISCRIMINATOR: u64 = u64::from_le_bytes([155, 234, 231, 146, 236, 158, 162, 30]);
+
+const TRADE_EVENT_DISCRIMINATOR: u64 = u64::from_le_bytes([189, 219, 127, 211, 78, 230, 97, 238]);
+const COMPLETE_EVENT_DISCRIMINATOR: u64 = u64::from_le_bytes([95, 114, 97, 156, 212, 46, 152, 8]);
+
+const PUMP_FUN_DECIMALS: u32 = 6;
+
+/// Leading fields of the Pump.fun `TradeEvent`; later fields are ignored.
+#[derive(BorshDeserialize)]
+struct TradeEvent {
+    mint: Pubkey,
+    sol_amount: u64,
+    token_amount: u64,
+    is_buy: bool,
+}
+
+/// The Pump.fun `CompleteEvent`, emitted by the buy that fills a curve.
+#[derive(BorshDeserialize)]
+struct CompleteEvent {
+    _user: Pubkey,
+    mint: Pubkey,
+    _bonding_curve: Pubkey,
+    _timestamp: i64,
+}
+
+/// Pump.fun bonding curves.
+///
+/// The curve account is the pool and holds the SOL side as plain lamports,
+/// the associated bonding curve token account holds the token side. Amounts
+/// come from the `TradeEvent` emitted under the instruction, falling back to
+/// the curve's lamport delta when there is none.
+///
+/// A buy that fills the curve emits a `CompleteEvent`, recorded as a
+/// `Complete` migration with the reserves left on the curve (the SOL side
+/// includes the curve account's rent). Completed curves then migrate in one
+/// of two ways: `withdraw` hands the reserves to the migration wallet, which
+/// seeds a Raydium pool with them, and `migrate` creates a PumpSwap pool
+/// itself (its program at 8, the pool at 9). The amounts migrated are the
+/// curve's balance diffs.
+pub struct PumpFunDecoder;
+
+impl DexDecoder for PumpFunDecoder {
+    fn program_id(&self) -> &'static str {
+        PUMP_FUN_PROGRAM_ID
+    }
+
+    fn decode(&self, ctx: &DecodeContext) -> Option<TradeInstruction> {
+        let name = match anchor_discriminator(ctx.data)? {
+            BUY_DISCRIMINATOR => "Buy",
+            SELL_DISCRIMINATOR => "Sell",
+            _ => return None,
+        };
+        let mint = ctx.input_accounts.get(2)?;
+        let bonding_curve = ctx.input_accounts.get(3)?;
+        let associated_bonding_curve = ctx.input_accounts.get(4)?;
+
+        let (amount_a, amount_b) = match find_trade_event(ctx, mint) {
+            Some(event) => {
+                let decimals = token_decimals(associated_bonding_curve, ctx.post_token_balances)
+                    .unwrap_or(PUMP_FUN_DECIMALS);
+                let token_amount = event.token_amount as i128;
+                let sol_amount = event.sol_amount as i128;
+                // pool perspective: a buy sends tokens out of the curve and SOL in
+                let (token_amount, sol_amount) = if event.is_buy {
+                    (-token_amount, sol_amount)
+                } else {
+                    (token_amount, -sol_amount)
+                };
+                (
+                    Some(TokenAmount {
+                        raw: token_amount,
+                        decimals,
+                    }),
+                    Some(TokenAmount {
+                        raw: sol_amount,
+                        decimals: SOL_DECIMALS,
+                    }),
+                )
+            }
+            None => {
+                let lamports = get_lamports_change(
+                    bonding_curve,
+                    ctx.accounts,
+                    ctx.pre_balances,
+                    ctx.post_balances,
+                )?;
+                (
+                    None,
+                    Some(TokenAmount {
+                        raw: lamports,
+                        decimals: SOL_DECIMALS,
+                    }),
+                )
+            }
+        };
+
+        Some(TradeInstruction {
+            dapp_address: String::from(PUMP_FUN_PROGRAM_ID),
+            dex: String::from("PUMPFUN"),
+            name: String::from(name),
+            amm: bonding_curve.to_string(),
+            vault_a: associated_bonding_curve.to_string(),
+            vault_b: bonding_curve.to_string(),
+            mint_a: mint.to_string(),
+            mint_b: String::from(WSOL_MINT),
+            amount_a,
+            amount_b,
+            ..Default::default()
+        })
+    }
+
+    fn decode_migration(&self, ctx: &DecodeContext) -> Option<MigrationInstruction> {
+        let (name, destination) = match anchor_discriminator(ctx.data)? {
+            BUY_DISCRIMINATOR => return decode_complete(ctx),
+            WITHDRAW_DISCRIMINATOR => ("Withdraw", None),
+            MIGRATE_DISCRIMINATOR => ("Migrate", Some((8, 9))),
+            _ => return None,
+        };
+        let (destination_program, destination_pool) = match destination {
+            Some((program_idx, pool_idx)) => (
+                ctx.input_accounts.get(program_idx)?.to_string(),
+                ctx.input_accounts.get(pool_idx)?.to_string(),
+            ),
+            None => Default::default(),
+        };
+        let bonding_curve = ctx.input_accounts.get(3)?;
+        let associated_bonding_curve = ctx.input_accounts.get(4)?;
+
+        let lamports =
+            get_lamports_change(bonding_curve, ctx.accounts, ctx.pre_balances, ctx.post_balances);
+        Some(MigrationInstruction {
+            dapp_address: String::from(PUMP_FUN_PROGRAM_ID),
+            name: String::from(name),
+            mint: ctx.input_accounts.get(2)?.to_string(),
+            curve: bonding_curve.to_string(),
+            destination_program,
+            destination_pool,
+            sol_amount: lamports.map(|lamports| TokenAmount {
+                raw: lamports.abs(),
+                decimals: SOL_DECIMALS,
+            }),
+            token_amount: token_balance_change(associated_bonding_curve, ctx).map(|amount| {
+                TokenAmount {
+                    raw: amount.raw.abs(),
+                    ..amount
+                }
+            }),
+        })
+    }
+}
+
+/// The `Complete` migration of a buy that filled the curve, `None` for any
+/// other buy.
+fn decode_complete(ctx: &DecodeContext) -> Option<MigrationInstruction> {
+    let mint = ctx.input_accounts.get(2)?;
+    let bonding_curve = ctx.input_accounts.get(3)?;
+    let associated_bonding_curve = ctx.input_accounts.get(4)?;
+    let mut events: Vec<CompleteEvent> =
+        cpi_events(ctx, PUMP_FUN_PROGRAM_ID, COMPLETE_EVENT_DISCRIMINATOR);
+    if events.is_empty() {
+        events = log_events(ctx, COMPLETE_EVENT_DISCRIMINATOR);
+    }
+    if !events.iter().any(|event| event.mint.to_string() == *mint) {
+        return None;
+    }
+
+    let lamports = ctx
+        .accounts
+        .iter()
+        .position(|account| account == bonding_curve)
+        .and_then(|idx| ctx.post_balances.get(idx));
+    let tokens = find_balance(associated_bonding_curve, ctx.post_token_balances);
+    Some(MigrationInstruction {
+        dapp_address: String::from(PUMP_FUN_PROGRAM_ID),
+        name: String::from("Complete"),
+        mint: mint.to_string(),
+        curve: bonding_curve.to_string(),
+        sol_amount: lamports.map(|&lamports| TokenAmount {
+            raw: lamports as i128,
+            decimals: SOL_DECIMALS,
+        }),
+        token_amount: tokens.and_then(|balance| {
+            Some(TokenAmount {
+                raw: balance.ui_token_amount.amount.parse().ok()?,
+                decimals: balance.ui_token_amount.decimals,
+            })
+        }),
+        ..Default::default()
+    })
+}
+
+fn find_trade_event(ctx: &DecodeContext, mint: &str) -> Option<TradeEvent> {
+    cpi_events::<TradeEvent>(ctx, PUMP_FUN_PROGRAM_ID, TRADE_EVENT_DISCRIMINATOR)
+        .into_iter()
+        .find(|event| event.mint.to_string() == mint)
+}
@@ -0,0 +1,178 @@
+use borsh::BorshDeserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::models::{TokenAmount, TradeInstruction};
+
+use super::{
+    anchor_discriminator, cpi_events, mint_decimals, token_decimals, ui_amount, DecodeContext,
+    DexDecoder,
+};
+
+pub const PUMP_SWAP_PROGRAM_ID: &str = "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA";
+
+const BUY_DISCRIMINATOR: u64 = u64::from_le_bytes([102, 6, 61, 18, 1, 218, 235, 234]);
+const SELL_DISCRIMINATOR: u64 = u64::from_le_bytes([51, 230, 133, 164, 1, 127, 131, 173]);
+
+const BUY_EVENT_DISCRIMINATOR: u64 = u64::from_le_bytes([103, 244, 82, 31, 44, 245, 119, 119]);
+const SELL_EVENT_DISCRIMINATOR: u64 = u64::from_le_bytes([62, 47, 55, 10, 165, 3, 220, 42]);
+
+/// Leading fields of the PumpSwap `BuyEvent`; later fields are ignored.
+#[derive(BorshDeserialize)]
+struct BuyEvent {
+    _timestamp: i64,
+    base_amount_out: u64,
+    _max_quote_amount_in: u64,
+    _user_base_token_reserves: u64,
+    _user_quote_token_reserves: u64,
+    pool_base_token_reserves: u64,
+    pool_quote_token_reserves: u64,
+    _quote_amount_in: u64,
+    _lp_fee_basis_points: u64,
+    lp_fee: u64,
+    _protocol_fee_basis_points: u64,
+    protocol_fee: u64,
+    /// What the pool's quote account received.
+    quote_amount_in_with_lp_fee: u64,
+    _user_quote_amount_in: u64,
+    pool: Pubkey,
+}
+
+/// Leading fields of the PumpSwap `SellEvent`; later fields are ignored.
+#[derive(BorshDeserialize)]
+struct SellEvent {
+    _timestamp: i64,
+    base_amount_in: u64,
+    _min_quote_amount_out: u64,
+    _user_base_token_reserves: u64,
+    _user_quote_token_reserves: u64,
+    pool_base_token_reserves: u64,
+    pool_quote_token_reserves: u64,
+    _quote_amount_out: u64,
+    _lp_fee_basis_points: u64,
+    lp_fee: u64,
+    _protocol_fee_basis_points: u64,
+    protocol_fee: u64,
+    /// What the pool's quote account paid out.
+    quote_amount_out_without_lp_fee: u64,
+    _user_quote_amount_out: u64,
+    pool: Pubkey,
+}
+
+/// Raw pool deltas, fee and pre-swap reserves read from a swap event.
+struct PoolSwap {
+    base_amount: i128,
+    quote_amount: i128,
+    fee: u64,
+    base_reserve: u64,
+    quote_reserve: u64,
+}
+
+/// PumpSwap, the AMM Pump.fun curves migrate to.
+///
+/// Pools pass the base and quote mints at 3/4 and their token accounts at
+/// 7/8. Amounts, fees and pre-swap reserves come from the pool's
+/// `BuyEvent`/`SellEvent`. Fees are charged in the quote token, so on sells
+/// they're converted to the base token at the swap's price.
+pub struct PumpSwapDecoder;
+
+impl DexDecoder for PumpSwapDecoder {
+    fn program_id(&self) -> &'static str {
+        PUMP_SWAP_PROGRAM_ID
+    }
+
+    fn decode(&self, ctx: &DecodeContext) -> Option<TradeInstruction> {
+        let discriminator = anchor_discriminator(ctx.data)?;
+        let name = match discriminator {
+            BUY_DISCRIMINATOR => "Buy",
+            SELL_DISCRIMINATOR => "Sell",
+            _ => return None,
+        };
+        let pool = ctx.input_accounts.first()?;
+        let base_mint = ctx.input_accounts.get(3)?;
+        let quote_mint = ctx.input_accounts.get(4)?;
+        let pool_base = ctx.input_accounts.get(7)?;
+        let pool_quote = ctx.input_accounts.get(8)?;
+
+        let swap = if discriminator == BUY_DISCRIMINATOR {
+            find_buy(ctx, pool)
+        } else {
+            find_sell(ctx, pool)
+        };
+        let decimals = |vault: &str, mint: &str| {
+            token_decimals(vault, ctx.post_token_balances).or_else(|| mint_decimals(mint, ctx))
+        };
+        let mut trade = TradeInstruction {
+            dapp_address: String::from(PUMP_SWAP_PROGRAM_ID),
+            dex: String::from("PUMPSWAP"),
+            name: String::from(name),
+            amm: pool.to_string(),
+            vault_a: pool_base.to_string(),
+            vault_b: pool_quote.to_string(),
+            mint_a: base_mint.to_string(),
+            mint_b: quote_mint.to_string(),
+            ..Default::default()
+        };
+        let (Some(swap), Some(decimals_base), Some(decimals_quote)) = (
+            swap,
+            decimals(pool_base, base_mint),
+            decimals(pool_quote, quote_mint),
+        ) else {
+            return Some(trade);
+        };
+
+        let base_amount = TokenAmount {
+            raw: swap.base_amount,
+            decimals: decimals_base,
+        };
+        let quote_amount = TokenAmount {
+            raw: swap.quote_amount,
+            decimals: decimals_quote,
+        };
+        let fee = ui_amount(swap.fee, decimals_quote);
+        let base_reserve = ui_amount(swap.base_reserve, decimals_base);
+        let quote_reserve = ui_amount(swap.quote_reserve, decimals_quote);
+        let post_base_reserve = base_reserve + base_amount.ui_amount();
+
+        trade.fee = if swap.base_amount <= 0 {
+            Some(fee)
+        } else if swap.quote_amount != 0 {
+            Some(fee * base_amount.ui_amount() / quote_amount.ui_amount().abs())
+        } else {
+            None
+        };
+        if post_base_reserve > 0.0 {
+            trade.post_price = Some((quote_reserve + quote_amount.ui_amount()) / post_base_reserve);
+        }
+        trade.reserve_a = Some(base_reserve);
+        trade.reserve_b = Some(quote_reserve);
+        trade.amount_a = Some(base_amount);
+        trade.amount_b = Some(quote_amount);
+        Some(trade)
+    }
+}
+
+fn find_buy(ctx: &DecodeContext, pool: &str) -> Option<PoolSwap> {
+    cpi_events::<BuyEvent>(ctx, PUMP_SWAP_PROGRAM_ID, BUY_EVENT_DISCRIMINATOR)
+        .into_iter()
+        .find(|event| event.pool.to_string() == pool)
+        .map(|event| PoolSwap {
+            base_amount: -(event.base_amount_out as i128),
+            quote_amount: event.quote_amount_in_with_lp_fee as i128,
+            fee: event.lp_fee.saturating_add(event.protocol_fee),
+            base_reserve: event.pool_base_token_reserves,
+            quote_reserve: event.pool_quote_token_reserves,
+        })
+}
+
+fn find_sell(ctx: &DecodeContext, pool: &str) -> Option<PoolSwap> {
+    cpi_events::<SellEvent>(ctx, PUMP_SWAP_PROGRAM_ID, SELL_EVENT_DISCRIMINATOR)
+        .into_iter()
+        .find(|event| event.pool.to_string() == pool)
+        .map(|event| PoolSwap {
+            base_amount: event.base_amount_in as i128,
+            quote_amount: -(event.quote_amount_out_without_lp_fee as i128),
+            fee: event.lp_fee.saturating_add(event.protocol_fee),
+            base_reserve: event.pool_base_token_reserves,
+            quote_reserve: event.pool_quote_token_reserves,
+        })
+}
@@ -0,0 +1,400 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use borsh::BorshDeserialize;
+
+use crate::models::{
+    LiquidityAction, LiquidityInstruction, PoolCreationInstruction, TokenAmount, TradeInstruction,
+};
+
+use super::{mint_decimals, token_decimals, ui_amount, DecodeContext, DexDecoder};
+
+pub const RAYDIUM_PROGRAM_ID: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
+const SERUM_PROGRAM_ID: &str = "srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX";
+
+const RAY_LOG_PREFIX: &str = "Program log: ray_log: ";
+const DEPOSIT_LOG: u8 = 1;
+const WITHDRAW_LOG: u8 = 2;
+const SWAP_BASE_IN_LOG: u8 = 3;
+const SWAP_BASE_OUT_LOG: u8 = 4;
+// `direction` of swaps paying coin in and taking pc out (1 is pc -> coin)
+const COIN_TO_PC: u64 = 2;
+
+#[derive(BorshDeserialize)]
+struct SwapBaseInLog {
+    amount_in: u64,
+    _minimum_out: u64,
+    direction: u64,
+    _user_source: u64,
+    pool_coin: u64,
+    pool_pc: u64,
+    out_amount: u64,
+}
+
+#[derive(BorshDeserialize)]
+struct SwapBaseOutLog {
+    _max_in: u64,
+    amount_out: u64,
+    direction: u64,
+    _user_source: u64,
+    pool_coin: u64,
+    pool_pc: u64,
+    deduct_in: u64,
+}
+
+/// Arguments of `initialize2`, after the instruction tag.
+#[derive(BorshDeserialize)]
+struct Initialize2 {
+    _nonce: u8,
+    _open_time: u64,
+    init_pc_amount: u64,
+    init_coin_amount: u64,
+}
+
+#[derive(BorshDeserialize)]
+struct DepositLog {
+    _max_coin: u64,
+    _max_pc: u64,
+    _base: u64,
+    _pool_coin: u64,
+    _pool_pc: u64,
+    _pool_lp: u64,
+    _calc_pnl_x: u128,
+    _calc_pnl_y: u128,
+    deduct_coin: u64,
+    deduct_pc: u64,
+    mint_lp: u64,
+}
+
+#[derive(BorshDeserialize)]
+struct WithdrawLog {
+    withdraw_lp: u64,
+    _user_lp: u64,
+    _pool_coin: u64,
+    _pool_pc: u64,
+    _pool_lp: u64,
+    _calc_pnl_x: u128,
+    _calc_pnl_y: u128,
+    out_coin: u64,
+    out_pc: u64,
+}
+
+/// A deposit or withdrawal as reported by `ray_log`, raw amounts. Vault
+/// deltas are positive for deposits.
+struct RayLiquidity {
+    coin: i128,
+    pc: i128,
+    lp: u64,
+}
+
+/// A swap as reported by `ray_log`, raw amounts.
+struct RaySwap {
+    amount_in: u64,
+    amount_out: u64,
+    coin_to_pc: bool,
+    /// Reserves before the swap.
+    pool_coin: u64,
+    pool_pc: u64,
+}
+
+/// Raydium AMM v4.
+///
+/// Amounts and pre-swap reserves come from the `ray_log` line the program
+/// writes for every swap, deposit and withdrawal; the balance diff is only
+/// used when it's missing.
+pub struct RaydiumDecoder;
+
+impl DexDecoder for RaydiumDecoder {
+    fn program_id(&self) -> &'static str {
+        RAYDIUM_PROGRAM_ID
+    }
+
+    fn decode(&self, ctx: &DecodeContext) -> Option<TradeInstruction> {
+        let name = match ctx.data.first()? {
+            9 => "SwapBaseIn",
+            11 => "SwapBaseOut",
+            _ => return None,
+        };
+        let (vault_a, vault_b) = resolve_vaults(&ctx.input_accounts)?;
+
+        let mut trade = TradeInstruction {
+            dapp_address: String::from(RAYDIUM_PROGRAM_ID),
+            dex: String::from("RAYDIUM"),
+            name: String::from(name),
+            amm: ctx.input_accounts.get(1)?.to_string(),
+            vault_a,
+            vault_b,
+            ..Default::default()
+        };
+
+        let decimals_coin = token_decimals(&trade.vault_a, ctx.post_token_balances);
+        let decimals_pc = token_decimals(&trade.vault_b, ctx.post_token_balances);
+        if let (Some(swap), Some(decimals_coin), Some(decimals_pc)) =
+            (parse_ray_log(ctx.logs), decimals_coin, decimals_pc)
+        {
+            // pool perspective: the input goes into the pool, the output leaves it
+            let (coin_amount, pc_amount) = if swap.coin_to_pc {
+                (swap.amount_in as i128, -(swap.amount_out as i128))
+            } else {
+                (-(swap.amount_out as i128), swap.amount_in as i128)
+            };
+            trade.amount_a = Some(TokenAmount {
+                raw: coin_amount,
+                decimals: decimals_coin,
+            });
+            trade.amount_b = Some(TokenAmount {
+                raw: pc_amount,
+                decimals: decimals_pc,
+            });
+            trade.reserve_a = Some(ui_amount(swap.pool_coin, decimals_coin));
+            trade.reserve_b = Some(ui_amount(swap.pool_pc, decimals_pc));
+        }
+
+        Some(trade)
+    }
+
+    fn decode_liquidity(&self, ctx: &DecodeContext) -> Option<LiquidityInstruction> {
+        let (name, action) = match ctx.data.first()? {
+            3 => ("Deposit", LiquidityAction::Add),
+            4 => ("Withdraw", LiquidityAction::Remove),
+            _ => return None,
+        };
+
+        // both pass the lp mint and the coin/pc vaults at 5/6/7
+        let mut liquidity = LiquidityInstruction {
+            dapp_address: String::from(RAYDIUM_PROGRAM_ID),
+            name: String::from(name),
+            action,
+            amm: ctx.input_accounts.get(1)?.to_string(),
+            lp_mint: ctx.input_accounts.get(5)?.to_string(),
+            vault_a: ctx.input_accounts.get(6)?.to_string(),
+            vault_b: ctx.input_accounts.get(7)?.to_string(),
+            ..Default::default()
+        };
+
+        let decimals_coin = token_decimals(&liquidity.vault_a, ctx.post_token_balances);
+        let decimals_pc = token_decimals(&liquidity.vault_b, ctx.post_token_balances);
+        if let (Some(log), Some(decimals_coin), Some(decimals_pc)) =
+            (parse_ray_liquidity_log(ctx.logs), decimals_coin, decimals_pc)
+        {
+            liquidity.amount_a = Some(TokenAmount {
+                raw: log.coin,
+                decimals: decimals_coin,
+            });
+            liquidity.amount_b = Some(TokenAmount {
+                raw: log.pc,
+                decimals: decimals_pc,
+            });
+            liquidity.lp_amount =
+                mint_decimals(&liquidity.lp_mint, ctx).map(|decimals| TokenAmount {
+                    raw: log.lp as i128,
+                    decimals,
+                });
+        }
+
+        Some(liquidity)
+    }
+
+    fn decode_pool_creation(&self, ctx: &DecodeContext) -> Option<PoolCreationInstruction> {
+        let (&tag, mut args) = ctx.data.split_first()?;
+        if tag != 1 {
+            return None;
+        }
+        let args = Initialize2::deserialize(&mut args).ok()?;
+
+        let mint_coin = ctx.input_accounts.get(8)?;
+        let mint_pc = ctx.input_accounts.get(9)?;
+        let amount = |raw: u64, mint: &str| {
+            mint_decimals(mint, ctx).map(|decimals| TokenAmount {
+                raw: raw as i128,
+                decimals,
+            })
+        };
+
+        Some(PoolCreationInstruction {
+            dapp_address: String::from(RAYDIUM_PROGRAM_ID),
+            name: String::from("Initialize2"),
+            amm: ctx.input_accounts.get(4)?.to_string(),
+            creator: ctx.input_accounts.get(17)?.to_string(),
+            mint_a: mint_coin.to_string(),
+            mint_b: mint_pc.to_string(),
+            vault_a: ctx.input_accounts.get(10)?.to_string(),
+            vault_b: ctx.input_accounts.get(11)?.to_string(),
+            lp_mint: ctx.input_accounts.get(7)?.to_string(),
+            amount_a: amount(args.init_coin_amount, mint_coin),
+            amount_b: amount(args.init_pc_amount, mint_pc),
+            ..Default::default()
+        })
+    }
+}
+
+/// Log type and record of the instruction's `ray_log` line.
+fn ray_log(logs: &[String]) -> Option<(u8, Vec<u8>)> {
+    let bytes = logs
+        .iter()
+        .find_map(|line| line.strip_prefix(RAY_LOG_PREFIX))
+        .and_then(|encoded| STANDARD.decode(encoded.trim()).ok())?;
+    let (&log_type, record) = bytes.split_first()?;
+    Some((log_type, record.to_vec()))
+}
+
+/// Finds and decodes the deposit or withdrawal record among the instruction's
+/// `ray_log` lines.
+fn parse_ray_liquidity_log(logs: &[String]) -> Option<RayLiquidity> {
+    let (log_type, record) = ray_log(logs)?;
+    let mut record = record.as_slice();
+
+    match log_type {
+        DEPOSIT_LOG => {
+            let log = DepositLog::deserialize(&mut record).ok()?;
+            Some(RayLiquidity {
+                coin: log.deduct_coin as i128,
+                pc: log.deduct_pc as i128,
+                lp: log.mint_lp,
+            })
+        }
+        WITHDRAW_LOG => {
+            let log = WithdrawLog::deserialize(&mut record).ok()?;
+            Some(RayLiquidity {
+                coin: -(log.out_coin as i128),
+                pc: -(log.out_pc as i128),
+                lp: log.withdraw_lp,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Finds and decodes the swap record among the instruction's `ray_log` lines.
+fn parse_ray_log(logs: &[String]) -> Option<RaySwap> {
+    let (log_type, record) = ray_log(logs)?;
+    let mut record = record.as_slice();
+
+    match log_type {
+        SWAP_BASE_IN_LOG => {
+            let log = SwapBaseInLog::deserialize(&mut record).ok()?;
+            Some(RaySwap {
+                amount_in: log.amount_in,
+                amount_out: log.out_amount,
+                coin_to_pc: log.direction == COIN_TO_PC,
+                pool_coin: log.pool_coin,
+                pool_pc: log.pool_pc,
+            })
+        }
+        SWAP_BASE_OUT_LOG => {
+            let log = SwapBaseOutLog::deserialize(&mut record).ok()?;
+            Some(RaySwap {
+                amount_in: log.deduct_in,
+                amount_out: log.amount_out,
+                coin_to_pc: log.direction == COIN_TO_PC,
+                pool_coin: log.pool_coin,
+                pool_pc: log.pool_pc,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Coin and pc vaults sit right before the Serum program account. Instructions
+/// that don't pass it are resolved by their account count instead (17 accounts
+/// without target orders, 18 with).
+fn resolve_vaults(input_accounts: &[String]) -> Option<(String, String)> {
+    let (base_idx, quote_idx) =
+        match input_accounts.iter().position(|add| add == SERUM_PROGRAM_ID) {
+            Some(pos) => (pos.checked_sub(2)?, pos - 1),
+            None => match input_accounts.len() {
+                17 => (4, 5),
+                18 => (5, 6),
+                _ => return None,
+            },
+        };
+
+    Some((
+        input_accounts.get(base_idx)?.to_string(),
+        input_accounts.get(quote_idx)?.to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `ray_log` line for a record made of the given fields.
+    fn log_line(log_type: u8, fields: &[u128], wide: &[usize]) -> Vec<String> {
+        let mut bytes = vec![log_type];
+        for (idx, field) in fields.iter().enumerate() {
+            if wide.contains(&idx) {
+                bytes.extend(field.to_le_bytes());
+            } else {
+                bytes.extend((*field as u64).to_le_bytes());
+            }
+        }
+        vec![
+            String::from("Program log: Instruction: Swap"),
+            format!("{}{}", RAY_LOG_PREFIX, STANDARD.encode(bytes)),
+        ]
+    }
+
+    #[test]
+    fn swap_base_in_log() {
+        // amount_in, minimum_out, direction, user_source, pool_coin, pool_pc, out_amount
+        let logs = log_line(
+            SWAP_BASE_IN_LOG,
+            &[100, 90, 2, 500, 10_000, 20_000, 95],
+            &[],
+        );
+        let swap = parse_ray_log(&logs).unwrap();
+        assert_eq!(swap.amount_in, 100);
+        assert_eq!(swap.amount_out, 95);
+        assert!(swap.coin_to_pc);
+        assert_eq!((swap.pool_coin, swap.pool_pc), (10_000, 20_000));
+    }
+
+    #[test]
+    fn swap_base_out_log() {
+        // max_in, amount_out, direction, user_source, pool_coin, pool_pc, deduct_in
+        let logs = log_line(
+            SWAP_BASE_OUT_LOG,
+            &[120, 80, 1, 500, 10_000, 20_000, 110],
+            &[],
+        );
+        let swap = parse_ray_log(&logs).unwrap();
+        assert_eq!(swap.amount_in, 110);
+        assert_eq!(swap.amount_out, 80);
+        assert!(!swap.coin_to_pc);
+    }
+
+    #[test]
+    fn liquidity_logs() {
+        // max_coin, max_pc, base, pool_coin, pool_pc, pool_lp, calc_pnl_x, calc_pnl_y,
+        // deduct_coin, deduct_pc, mint_lp
+        let deposit = log_line(
+            DEPOSIT_LOG,
+            &[1, 2, 0, 3, 4, 5, 6, 7, 300, 600, 42],
+            &[6, 7],
+        );
+        let deposit = parse_ray_liquidity_log(&deposit).unwrap();
+        assert_eq!((deposit.coin, deposit.pc, deposit.lp), (300, 600, 42));
+
+        // withdraw_lp, user_lp, pool_coin, pool_pc, pool_lp, calc_pnl_x, calc_pnl_y,
+        // out_coin, out_pc
+        let withdraw = log_line(WITHDRAW_LOG, &[42, 50, 3, 4, 5, 6, 7, 300, 600], &[5, 6]);
+        let withdraw = parse_ray_liquidity_log(&withdraw).unwrap();
+        assert_eq!((withdraw.coin, withdraw.pc, withdraw.lp), (-300, -600, 42));
+    }
+
+    #[test]
+    fn log_types_dont_cross() {
+        let deposit = log_line(
+            DEPOSIT_LOG,
+            &[1, 2, 0, 3, 4, 5, 6, 7, 300, 600, 42],
+            &[6, 7],
+        );
+        assert!(parse_ray_log(&deposit).is_none());
+        let swap = log_line(
+            SWAP_BASE_IN_LOG,
+            &[100, 90, 2, 500, 10_000, 20_000, 95],
+            &[],
+        );
+        assert!(parse_ray_liquidity_log(&swap).is_none());
+        assert!(parse_ray_log(&[String::from("Program log: ray_log: not base64")]).is_none());
+    }
+}
@@ -0,0 +1,54 @@
+use crate::models::TradeInstruction;
+
+use super::{anchor_discriminator, in_pool_order, resolve_mint, DecodeContext, DexDecoder};
+
+pub const RAYDIUM_CLMM_PROGRAM_ID: &str = "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK";
+
+const SWAP_DISCRIMINATOR: u64 = u64::from_le_bytes([248, 198, 158, 145, 225, 117, 135, 200]);
+const SWAP_V2_DISCRIMINATOR: u64 = u64::from_le_bytes([43, 4, 237, 11, 26, 201, 30, 98]);
+
+/// Raydium concentrated liquidity pools.
+///
+/// `swap` and `swap_v2` share the leading accounts: payer, amm config, pool
+/// state, user input/output token accounts, then the input and output vaults.
+/// `swap_v2` also passes the vault mints, which may be Token-2022; for `swap`
+/// they come from the vault balances. Pools are created with
+/// `token_mint_0 < token_mint_1`, so the vaults are reported in that order
+/// (token 0 as base) whatever the swap direction.
+pub struct RaydiumClmmDecoder;
+
+impl DexDecoder for RaydiumClmmDecoder {
+    fn program_id(&self) -> &'static str {
+        RAYDIUM_CLMM_PROGRAM_ID
+    }
+
+    fn decode(&self, ctx: &DecodeContext) -> Option<TradeInstruction> {
+        let (name, mint_in, mint_out) = match anchor_discriminator(ctx.data)? {
+            SWAP_DISCRIMINATOR => ("Swap", String::new(), String::new()),
+            SWAP_V2_DISCRIMINATOR => (
+                "SwapV2",
+                ctx.input_accounts.get(11)?.to_string(),
+                ctx.input_accounts.get(12)?.to_string(),
+            ),
+            _ => return None,
+        };
+        let vault_in = ctx.input_accounts.get(5)?;
+        let vault_out = ctx.input_accounts.get(6)?;
+        let ((vault_a, mint_a), (vault_b, mint_b)) = in_pool_order(
+            (vault_in.to_string(), resolve_mint(&mint_in, vault_in, ctx).unwrap_or_default()),
+            (vault_out.to_string(), resolve_mint(&mint_out, vault_out, ctx).unwrap_or_default()),
+        );
+
+        Some(TradeInstruction {
+            dapp_address: String::from(RAYDIUM_CLMM_PROGRAM_ID),
+            dex: String::from("RAYDIUM"),
+            name: String::from(name),
+            amm: ctx.input_accounts.get(2)?.to_string(),
+            vault_a,
+            vault_b,
+            mint_a,
+            mint_b,
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,127 @@
+use borsh::BorshDeserialize;
+
+use crate::models::{
+    LiquidityAction, LiquidityInstruction, PoolCreationInstruction, TokenAmount, TradeInstruction,
+};
+
+use super::{
+    anchor_discriminator, in_pool_order, mint_decimals, token_balance_change, DecodeContext,
+    DexDecoder,
+};
+
+pub const RAYDIUM_CPMM_PROGRAM_ID: &str = "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C";
+
+const SWAP_BASE_INPUT_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([143, 190, 90, 218, 196, 30, 51, 222]);
+const SWAP_BASE_OUTPUT_DISCRIMINATOR: u64 =
+    u64::from_le_bytes([55, 217, 98, 86, 163, 74, 180, 173]);
+const DEPOSIT_DISCRIMINATOR: u64 = u64::from_le_bytes([242, 35, 198, 137, 82, 225, 242, 182]);
+const WITHDRAW_DISCRIMINATOR: u64 = u64::from_le_bytes([183, 18, 70, 156, 148, 109, 161, 34]);
+const INITIALIZE_DISCRIMINATOR: u64 = u64::from_le_bytes([175, 175, 109, 31, 13, 152, 155, 237]);
+
+/// Arguments of `initialize`.
+#[derive(BorshDeserialize)]
+struct Initialize {
+    init_amount_0: u64,
+    init_amount_1: u64,
+    _open_time: u64,
+}
+
+/// Raydium constant product pools (CPMM).
+///
+/// Both swap instructions take the input/output vaults at 6/7 and the
+/// input/output mints at 10/11. Either side can be a Token-2022 mint, so the
+/// mints are read from the instruction instead of the vault balances. Pools
+/// are created with `token_0_mint < token_1_mint`, so swap vaults are reported
+/// in that order (token 0 as base) whatever the swap direction. Deposits and
+/// withdrawals take the token 0/1 vaults and mints at the same positions, with
+/// the provider's LP account at 3 and the LP mint at 12. `initialize` has its
+/// own layout, with the creator first.
+pub struct RaydiumCpmmDecoder;
+
+impl DexDecoder for RaydiumCpmmDecoder {
+    fn program_id(&self) -> &'static str {
+        RAYDIUM_CPMM_PROGRAM_ID
+    }
+
+    fn decode(&self, ctx: &DecodeContext) -> Option<TradeInstruction> {
+        let name = match anchor_discriminator(ctx.data)? {
+            SWAP_BASE_INPUT_DISCRIMINATOR => "SwapBaseInput",
+            SWAP_BASE_OUTPUT_DISCRIMINATOR => "SwapBaseOutput",
+            _ => return None,
+        };
+        let ((vault_a, mint_a), (vault_b, mint_b)) = in_pool_order(
+            (ctx.input_accounts.get(6)?.to_string(), ctx.input_accounts.get(10)?.to_string()),
+            (ctx.input_accounts.get(7)?.to_string(), ctx.input_accounts.get(11)?.to_string()),
+        );
+
+        Some(TradeInstruction {
+            dapp_address: String::from(RAYDIUM_CPMM_PROGRAM_ID),
+            dex: String::from("RAYDIUM"),
+            name: String::from(name),
+            amm: ctx.input_accounts.get(3)?.to_string(),
+            vault_a,
+            vault_b,
+            mint_a,
+            mint_b,
+            ..Default::default()
+        })
+    }
+
+    fn decode_liquidity(&self, ctx: &DecodeContext) -> Option<LiquidityInstruction> {
+        let (name, action) = match anchor_discriminator(ctx.data)? {
+            DEPOSIT_DISCRIMINATOR => ("Deposit", LiquidityAction::Add),
+            WITHDRAW_DISCRIMINATOR => ("Withdraw", LiquidityAction::Remove),
+            _ => return None,
+        };
+
+        let owner_lp = ctx.input_accounts.get(3)?;
+        Some(LiquidityInstruction {
+            dapp_address: String::from(RAYDIUM_CPMM_PROGRAM_ID),
+            name: String::from(name),
+            action,
+            amm: ctx.input_accounts.get(2)?.to_string(),
+            vault_a: ctx.input_accounts.get(6)?.to_string(),
+            vault_b: ctx.input_accounts.get(7)?.to_string(),
+            mint_a: ctx.input_accounts.get(10)?.to_string(),
+            mint_b: ctx.input_accounts.get(11)?.to_string(),
+            lp_mint: ctx.input_accounts.get(12)?.to_string(),
+            lp_amount: token_balance_change(owner_lp, ctx).map(|amount| TokenAmount {
+                raw: amount.raw.abs(),
+                ..amount
+            }),
+            ..Default::default()
+        })
+    }
+
+    fn decode_pool_creation(&self, ctx: &DecodeContext) -> Option<PoolCreationInstruction> {
+        if anchor_discriminator(ctx.data)? != INITIALIZE_DISCRIMINATOR {
+            return None;
+        }
+        let args = Initialize::deserialize(&mut ctx.data.get(8..)?).ok()?;
+
+        let mint_0 = ctx.input_accounts.get(4)?;
+        let mint_1 = ctx.input_accounts.get(5)?;
+        let amount = |raw: u64, mint: &str| {
+            mint_decimals(mint, ctx).map(|decimals| TokenAmount {
+                raw: raw as i128,
+                decimals,
+            })
+        };
+
+        Some(PoolCreationInstruction {
+            dapp_address: String::from(RAYDIUM_CPMM_PROGRAM_ID),
+            name: String::from("Initialize"),
+            amm: ctx.input_accounts.get(3)?.to_string(),
+            creator: ctx.input_accounts.first()?.to_string(),
+            mint_a: mint_0.to_string(),
+            mint_b: mint_1.to_string(),
+            vault_a: ctx.input_accounts.get(10)?.to_string(),
+            vault_b: ctx.input_accounts.get(11)?.to_string(),
+            lp_mint: ctx.input_accounts.get(6)?.to_string(),
+            amount_a: amount(args.init_amount_0, mint_0),
+            amount_b: amount(args.init_amount_1, mint_1),
+            ..Default::default()
+        })
+    }
+}
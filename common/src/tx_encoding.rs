@@ -0,0 +1,413 @@
+use serde_json::Value;
+use solana_sdk::{
+    bs58, message::MessageHeader, pubkey::Pubkey, transaction::VersionedTransaction,
+};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, parse_accounts::ParsedAccountSource, EncodedTransaction,
+    UiAddressTableLookup, UiCompiledInstruction, UiInstruction, UiMessage, UiParsedInstruction,
+    UiParsedMessage, UiRawMessage, UiTransactionStatusMeta,
+};
+
+use crate::jito::SYSTEM_PROGRAM_ID;
+use crate::utils::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+/// A transaction brought to the shape of the `json` encoding, which is what
+/// the decoding passes work on.
+pub struct RawTransaction {
+    pub signature: String,
+    pub message: UiRawMessage,
+    /// Static account keys followed by the loaded writable and readonly
+    /// addresses, the order instructions index into.
+    pub all_addresses: Vec<String>,
+}
+
+/// Converts any transaction encoding to a raw message:
+/// - `json` is taken as is,
+/// - `base58`/`base64` are decoded to a `VersionedTransaction` first,
+/// - `jsonParsed` instructions are compiled back against the account list;
+///   token and System transfers are re-encoded from their parsed form, other
+///   parsed instructions keep only their program.
+///
+/// Parsed inner instructions in `meta` are compiled back the same way.
+/// `None` for account-only encodings, which carry no instructions.
+pub fn to_raw_transaction(
+    transaction: EncodedTransaction,
+    meta: &mut UiTransactionStatusMeta,
+) -> Option<RawTransaction> {
+    let (signature, message, all_addresses) = match transaction {
+        EncodedTransaction::Json(ui_transaction) => {
+            let signature = ui_transaction.signatures.first()?.clone();
+            match ui_transaction.message {
+                UiMessage::Raw(message) => {
+                    let all_addresses = with_loaded_addresses(&message, meta);
+                    (signature, message, all_addresses)
+                }
+                // parsed account keys already list the loaded addresses
+                UiMessage::Parsed(message) => {
+                    let all_addresses: Vec<String> = message
+                        .account_keys
+                        .iter()
+                        .map(|account| account.pubkey.clone())
+                        .collect();
+                    (signature, from_parsed_message(message, &all_addresses), all_addresses)
+                }
+            }
+        }
+        EncodedTransaction::LegacyBinary(_) | EncodedTransaction::Binary(..) => {
+            let transaction = transaction.decode()?;
+            let message = from_versioned_transaction(&transaction);
+            let all_addresses = with_loaded_addresses(&message, meta);
+            (transaction.signatures.first()?.to_string(), message, all_addresses)
+        }
+        EncodedTransaction::Accounts(_) => return None,
+    };
+
+    if let OptionSerializer::Some(inner_instructions) = &mut meta.inner_instructions {
+        for inner in inner_instructions.iter_mut() {
+            inner.instructions = inner
+                .instructions
+                .drain(..)
+                .filter_map(|instruction| compile_instruction(instruction, &all_addresses))
+                .map(UiInstruction::Compiled)
+                .collect();
+        }
+    }
+
+    Some(RawTransaction {
+        signature,
+        message,
+        all_addresses,
+    })
+}
+
+fn with_loaded_addresses(message: &UiRawMessage, meta: &UiTransactionStatusMeta) -> Vec<String> {
+    let mut all_addresses = message.account_keys.clone();
+    if let OptionSerializer::Some(loaded_addresses) = &meta.loaded_addresses {
+        all_addresses.extend(loaded_addresses.writable.iter().cloned());
+        all_addresses.extend(loaded_addresses.readonly.iter().cloned());
+    }
+    all_addresses
+}
+
+fn from_versioned_transaction(transaction: &VersionedTransaction) -> UiRawMessage {
+    let message = &transaction.message;
+    UiRawMessage {
+        header: *message.header(),
+        account_keys: message
+            .static_account_keys()
+            .iter()
+            .map(|key| key.to_string())
+            .collect(),
+        recent_blockhash: message.recent_blockhash().to_string(),
+        instructions: message
+            .instructions()
+            .iter()
+            .map(|instruction| UiCompiledInstruction::from(instruction, None))
+            .collect(),
+        address_table_lookups: message
+            .address_table_lookups()
+            .map(|lookups| lookups.iter().map(UiAddressTableLookup::from).collect()),
+    }
+}
+
+fn from_parsed_message(message: UiParsedMessage, all_addresses: &[String]) -> UiRawMessage {
+    let static_accounts: Vec<_> = message
+        .account_keys
+        .iter()
+        .filter(|account| !matches!(account.source, Some(ParsedAccountSource::LookupTable)))
+        .collect();
+    let count = |filter: fn(bool, bool) -> bool| {
+        static_accounts
+            .iter()
+            .filter(|account| filter(account.signer, account.writable))
+            .count() as u8
+    };
+
+    UiRawMessage {
+        header: MessageHeader {
+            num_required_signatures: count(|signer, _| signer),
+            num_readonly_signed_accounts: count(|signer, writable| signer && !writable),
+            num_readonly_unsigned_accounts: count(|signer, writable| !signer && !writable),
+        },
+        account_keys: static_accounts
+            .iter()
+            .map(|account| account.pubkey.clone())
+            .collect(),
+        recent_blockhash: message.recent_blockhash,
+        instructions: message
+            .instructions
+            .into_iter()
+            .filter_map(|instruction| compile_instruction(instruction, all_addresses))
+            .collect(),
+        address_table_lookups: message.address_table_lookups,
+    }
+}
+
+/// Turns a parsed instruction back into account indices and bs58 data.
+fn compile_instruction(
+    instruction: UiInstruction,
+    all_addresses: &[String],
+) -> Option<UiCompiledInstruction> {
+    let index_of = |address: &str| {
+        all_addresses
+            .iter()
+            .position(|candidate| candidate == address)
+            .map(|idx| idx as u8)
+    };
+
+    match instruction {
+        UiInstruction::Compiled(compiled) => Some(compiled),
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(decoded)) => {
+            Some(UiCompiledInstruction {
+                program_id_index: index_of(&decoded.program_id)?,
+                accounts: decoded
+                    .accounts
+                    .iter()
+                    .map(|account| index_of(account))
+                    .collect::<Option<_>>()?,
+                data: decoded.data,
+                stack_height: decoded.stack_height,
+            })
+        }
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed)) => {
+            let encoded = match parsed.program_id.as_str() {
+                TOKEN_PROGRAM_ID | TOKEN_2022_PROGRAM_ID => encode_token_transfer(&parsed.parsed),
+                SYSTEM_PROGRAM_ID => encode_system_transfer(&parsed.parsed),
+                _ => None,
+            };
+            let (accounts, data) = encoded
+                .and_then(|(accounts, data)| {
+                    let accounts = accounts
+                        .into_iter()
+                        .map(index_of)
+                        .collect::<Option<Vec<u8>>>()?;
+                    Some((accounts, data))
+                })
+                .unwrap_or_default();
+            Some(UiCompiledInstruction {
+                program_id_index: index_of(&parsed.program_id)?,
+                accounts,
+                data: bs58::encode(data).into_string(),
+                stack_height: parsed.stack_height,
+            })
+        }
+    }
+}
+
+/// Accounts and instruction data of a parsed `transfer`, `transferChecked`
+/// or `transferCheckedWithFee`, the token instructions amounts are read from.
+fn encode_token_transfer(parsed: &Value) -> Option<(Vec<&str>, Vec<u8>)> {
+    let info = &parsed["info"];
+    let account = |name: &str| info[name].as_str();
+    let authority = account("authority").or(account("multisigAuthority"))?;
+    let amount = |value: &Value| value.as_str()?.parse::<u64>().ok();
+    let decimals = || info["tokenAmount"]["decimals"].as_u64().map(|decimals| decimals as u8);
+
+    match parsed["type"].as_str()? {
+        "transfer" => {
+            let mut data = vec![3];
+            data.extend(amount(&info["amount"])?.to_le_bytes());
+            Some((vec![account("source")?, account("destination")?, authority], data))
+        }
+        "transferChecked" => {
+            let mut data = vec![12];
+            data.extend(amount(&info["tokenAmount"]["amount"])?.to_le_bytes());
+            data.push(decimals()?);
+            let accounts = vec![
+                account("source")?,
+                account("mint")?,
+                account("destination")?,
+                authority,
+            ];
+            Some((accounts, data))
+        }
+        "transferCheckedWithFee" => {
+            let mut data = vec![26, 1];
+            data.extend(amount(&info["tokenAmount"]["amount"])?.to_le_bytes());
+            data.push(decimals()?);
+            data.extend(amount(&info["feeAmount"]["amount"])?.to_le_bytes());
+            let accounts = vec![
+                account("source")?,
+                account("mint")?,
+                account("destination")?,
+                authority,
+            ];
+            Some((accounts, data))
+        }
+        _ => None,
+    }
+}
+
+/// Accounts and instruction data of a parsed System `transfer` or
+/// `transferWithSeed`, the lamport movements tips are read from.
+fn encode_system_transfer(parsed: &Value) -> Option<(Vec<&str>, Vec<u8>)> {
+    let info = &parsed["info"];
+    let account = |name: &str| info[name].as_str();
+    let lamports = info["lamports"].as_u64()?;
+
+    match parsed["type"].as_str()? {
+        "transfer" => {
+            let mut data = 2u32.to_le_bytes().to_vec();
+            data.extend(lamports.to_le_bytes());
+            Some((vec![account("source")?, account("destination")?], data))
+        }
+        "transferWithSeed" => {
+            let seed = account("sourceSeed")?;
+            let owner = account("sourceOwner")?.parse::<Pubkey>().ok()?;
+            let mut data = 11u32.to_le_bytes().to_vec();
+            data.extend(lamports.to_le_bytes());
+            data.extend((seed.len() as u64).to_le_bytes());
+            data.extend(seed.as_bytes());
+            data.extend(owner.to_bytes());
+            let accounts = vec![
+                account("source")?,
+                account("sourceBase")?,
+                account("destination")?,
+            ];
+            Some((accounts, data))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{
+        address_lookup_table::AddressLookupTableAccount,
+        hash::Hash,
+        instruction::{AccountMeta, Instruction},
+        message::{
+            v0::{self, LoadedAddresses},
+            Message, VersionedMessage,
+        },
+        signature::{Keypair, Signer},
+        system_instruction,
+        transaction::Transaction,
+    };
+    use solana_transaction_status::{
+        EncodableWithMeta, TransactionStatusMeta, UiTransactionEncoding,
+    };
+
+    use super::*;
+
+    /// A signed legacy transaction with a System transfer, a System transfer
+    /// with seed and an SPL Token `transfer`.
+    fn fixture() -> VersionedTransaction {
+        let payer = Keypair::new();
+        let base = Keypair::new();
+        let owner = Pubkey::new_unique();
+        let seeded = Pubkey::create_with_seed(&base.pubkey(), "seed", &owner).unwrap();
+        let tip = Pubkey::new_unique();
+        let source = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+
+        let mut token_data = vec![3];
+        token_data.extend(1_500u64.to_le_bytes());
+        let token_transfer = Instruction::new_with_bytes(
+            TOKEN_PROGRAM_ID.parse().unwrap(),
+            &token_data,
+            vec![
+                AccountMeta::new(source, false),
+                AccountMeta::new(destination, false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+        let instructions = [
+            system_instruction::transfer(&payer.pubkey(), &tip, 10_000),
+            system_instruction::transfer_with_seed(
+                &seeded,
+                &base.pubkey(),
+                String::from("seed"),
+                &owner,
+                &tip,
+                20_000,
+            ),
+            token_transfer,
+        ];
+        let message = Message::new(&instructions, Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer, &base], message, Hash::new_unique());
+        VersionedTransaction::from(transaction)
+    }
+
+    /// A v0 transaction paying a tip to an account loaded from a lookup
+    /// table, with the meta that lists the loaded address.
+    fn v0_fixture() -> (VersionedTransaction, TransactionStatusMeta) {
+        let payer = Keypair::new();
+        let tip = Pubkey::new_unique();
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![tip],
+        };
+        let instructions = [system_instruction::transfer(&payer.pubkey(), &tip, 10_000)];
+        let message =
+            v0::Message::try_compile(&payer.pubkey(), &instructions, &[table], Hash::new_unique())
+                .unwrap();
+        let transaction =
+            VersionedTransaction::try_new(VersionedMessage::V0(message), &[&payer]).unwrap();
+        let status_meta = TransactionStatusMeta {
+            loaded_addresses: LoadedAddresses {
+                writable: vec![tip],
+                readonly: vec![],
+            },
+            ..Default::default()
+        };
+        (transaction, status_meta)
+    }
+
+    fn decode(
+        transaction: &VersionedTransaction,
+        status_meta: &TransactionStatusMeta,
+        encoding: UiTransactionEncoding,
+    ) -> RawTransaction {
+        let mut meta = UiTransactionStatusMeta::from(status_meta.clone());
+        let encoded = transaction.encode_with_meta(encoding, status_meta);
+        to_raw_transaction(encoded, &mut meta).unwrap()
+    }
+
+    #[test]
+    fn json_parsed_matches_json() {
+        let transaction = fixture();
+        let meta = TransactionStatusMeta::default();
+        let json = decode(&transaction, &meta, UiTransactionEncoding::Json);
+        let parsed = decode(&transaction, &meta, UiTransactionEncoding::JsonParsed);
+
+        assert_eq!(parsed.signature, json.signature);
+        assert_eq!(parsed.all_addresses, json.all_addresses);
+        assert_eq!(parsed.message, json.message);
+    }
+
+    #[test]
+    fn binary_encodings_match_json() {
+        let transaction = fixture();
+        let meta = TransactionStatusMeta::default();
+        let json = decode(&transaction, &meta, UiTransactionEncoding::Json);
+        for encoding in [
+            UiTransactionEncoding::Binary,
+            UiTransactionEncoding::Base58,
+            UiTransactionEncoding::Base64,
+        ] {
+            let binary = decode(&transaction, &meta, encoding);
+            assert_eq!(binary.signature, json.signature);
+            assert_eq!(binary.all_addresses, json.all_addresses);
+            assert_eq!(binary.message, json.message);
+        }
+    }
+
+    #[test]
+    fn loaded_addresses_follow_static_keys_in_every_encoding() {
+        let (transaction, meta) = v0_fixture();
+        let tip = meta.loaded_addresses.writable[0].to_string();
+        let json = decode(&transaction, &meta, UiTransactionEncoding::Json);
+        assert_eq!(json.all_addresses.last(), Some(&tip));
+        assert!(!json.message.account_keys.contains(&tip));
+
+        for encoding in [
+            UiTransactionEncoding::Base64,
+            UiTransactionEncoding::JsonParsed,
+        ] {
+            let decoded = decode(&transaction, &meta, encoding);
+            assert_eq!(decoded.all_addresses, json.all_addresses);
+            assert_eq!(decoded.message, json.message);
+        }
+    }
+}
@@ -1,98 +1,507 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use anyhow::Result;
-use borsh::BorshDeserialize;
-use solana_sdk::{address_lookup_table::program, bs58};
+use solana_sdk::{bs58, instruction::InstructionError, transaction::TransactionError};
 use solana_transaction_status::{
-    EncodedConfirmedBlock, EncodedTransactionWithStatusMeta, UiInnerInstructions, UiInstruction,
-    UiParsedInstruction,
+    EncodedTransactionWithStatusMeta, UiCompiledInstruction, UiInnerInstructions, UiInstruction,
+    UiRawMessage, UiTransactionStatusMeta, UiTransactionTokenBalance,
 };
-use spl_token::instruction::TokenInstruction;
 
 use crate::{
-    models::{PoolData, TokenBalance, TradeData, UiTokenAmount},
-    trade_parser::get_trade_instruction,
-    utils::{convert_to_date, get_amount, get_amt, get_mint, get_signer_balance_change},
+    compute_budget::parse_compute_budget,
+    errors::DecodeError,
+    jito::jito_tip_lamports,
+    models::{
+        FailedSwap, InnerInstruction, InnerInstructions, LiquidityEvent, LiquidityInstruction,
+        Migration, MigrationInstruction, PoolCreated, PoolCreationInstruction, RouteInstruction,
+        RouteTrade, SkippedCounts, TokenAmount, TokenBalance, TradeData, UiTokenAmount,
+    },
+    program_logs::{match_invocations, split_invocations, InvocationLogs},
+    tx_encoding::{to_raw_transaction, RawTransaction},
+    trade_parser::{
+        mint_decimals, resolve_mint, token_balance_change, trade_direction, trader_accounts,
+        transfer_amount, ui_amount, DecodeContext, DexDecoder, DECODERS,
+    },
+    utils::{convert_to_date, get_signer_balance_change, prepare_input_accounts},
 };
 
-const RAYDIUM_PROGRAM_ID: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
-const JUPITER_PROGRAM_ID: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
-const METEORA_PROGRAM_ID: &str = "Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB";
-const METEORA_DLMM_PROGRAM_ID: &str = "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo";
-const ORCA_PROGRAM_ID: &str = "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc";
-const SERUM_ADD: &str = "srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX";
+/// Everything decoded from one transaction.
+#[derive(Default)]
+pub struct DecodedTx {
+    pub trades: Vec<TradeData>,
+    pub liquidity_events: Vec<LiquidityEvent>,
+    pub pools_created: Vec<PoolCreated>,
+    pub migrations: Vec<Migration>,
+    /// Routes as reported by an aggregator; empty when the transaction
+    /// didn't go through one.
+    pub routes: Vec<RouteTrade>,
+}
 
+/// Decodes the swaps, liquidity changes, new pools and curve migrations of a
+/// successful transaction.
+/// Instructions that can't be decoded are left out and counted in
+/// `skipped`; the error is returned when the transaction itself can't be
+/// unpacked.
 pub async fn process_tx(
     trx: EncodedTransactionWithStatusMeta,
     slot: u64,
     timestamp: i64,
-) -> Option<Vec<TradeData>> {
-    let trx_meta = trx.meta.unwrap();
-    if trx_meta.err.is_some() {
-        return None;
+    skipped: &mut SkippedCounts,
+) -> Result<DecodedTx, DecodeError> {
+    // a failed transaction moved nothing: it belongs to the failed-swaps pass
+    // and isn't worth unpacking (or counting as skipped when it can't be)
+    if trx.meta.as_ref().is_some_and(|meta| meta.err.is_some()) {
+        return Ok(DecodedTx::default());
+    }
+    let UnpackedTx {
+        signature,
+        msg,
+        meta: trx_meta,
+        all_addresses,
+        pre_token_balances: pre_token_balances_vec,
+        post_token_balances: post_token_balances_vec,
+    } = unpack_tx(trx)?;
+    let signers = signers(&msg, &all_addresses);
+
+    let pre_balances = trx_meta.pre_balances;
+    let post_balances = trx_meta.post_balances;
+    let compute_budget = parse_compute_budget(&msg.instructions, &all_addresses);
+    let cu_consumed = Option::from(trx_meta.compute_units_consumed.clone()).unwrap_or(0);
+
+    // decoded rows keyed by the top-level instruction they belong to and their
+    // position among its inner instructions
+    let mut trades: Vec<(u32, Option<usize>, TradeData)> = vec![];
+    let mut liquidity_events: Vec<LiquidityEvent> = vec![];
+    let mut pools_created: Vec<PoolCreated> = vec![];
+    let mut migrations: Vec<Migration> = vec![];
+    let mut routes: Vec<RouteTrade> = vec![];
+    // top-level instructions a router decoder recognised
+    let mut routed: HashSet<u32> = HashSet::new();
+
+    let fee = trx_meta.fee;
+
+    let inners: Vec<InnerInstructions> = trx_meta
+        .inner_instructions
+        .clone()
+        .unwrap_or(vec![])
+        .iter()
+        .map(decode_inner_instructions)
+        .collect();
+    let jito_tip = jito_tip_lamports(&msg.instructions, &inners, &all_addresses);
+
+    let log_messages = trx_meta.log_messages.clone().unwrap_or(vec![]);
+    let invocations = split_invocations(&log_messages);
+    let logs = instruction_logs(&msg.instructions, &inners, &all_addresses, &invocations);
+
+    // iterate over inners
+    for inner in inners.iter() {
+        // program of the top-level instruction these were invoked from (e.g. Jupiter)
+        let outer_program = msg
+            .instructions
+            .get(inner.index as usize)
+            .and_then(|inst| all_addresses.get(inst.program_id_index as usize));
+
+        for (idx, inner_inst) in inner.instructions.iter().enumerate() {
+            let decoder = match all_addresses
+                .get(inner_inst.program_id_index as usize)
+                .and_then(|program_add| DECODERS.get(program_add))
+            {
+                Some(decoder) => decoder,
+                None => continue,
+            };
+
+            let ctx = DecodeContext {
+                data: &inner_inst.data,
+                input_accounts: prepare_input_accounts(&inner_inst.accounts, &all_addresses),
+                accounts: &all_addresses,
+                signers,
+                pre_token_balances: &pre_token_balances_vec,
+                post_token_balances: &post_token_balances_vec,
+                pre_balances: &pre_balances,
+                post_balances: &post_balances,
+                inner_instructions: nested_instructions(&inner.instructions, idx),
+                logs: logs.get(&(inner.index, Some(idx))).copied().unwrap_or(&[]),
+                is_inner: true,
+            };
+
+            let position = InstructionPosition {
+                index: inner.index,
+                inner_index: Some(idx as u32),
+                stack_height: inner_inst.stack_height,
+                outer_program,
+                invoking_program: invoking_program(&inner.instructions, idx, &all_addresses)
+                    .or(outer_program),
+            };
+            match build_trade_data(decoder, &ctx, &position, timestamp, slot, &signature, fee).await
+            {
+                Ok(legs) => {
+                    trades.extend(legs.into_iter().map(|trade| (inner.index, Some(idx), trade)))
+                }
+                Err(err) => skipped.record(&err),
+            }
+            if let Some(liquidity) = decoder.decode_liquidity(&ctx) {
+                match build_liquidity_event(
+                    liquidity, &ctx, &position, timestamp, slot, &signature, fee,
+                )
+                .await
+                {
+                    Ok(event) => liquidity_events.push(event),
+                    Err(err) => skipped.record(&err),
+                }
+            }
+            if let Some(pool) = decoder.decode_pool_creation(&ctx) {
+                match build_pool_created(pool, &ctx, &position, timestamp, slot, &signature).await
+                {
+                    Ok(pool) => pools_created.push(pool),
+                    Err(err) => skipped.record(&err),
+                }
+            }
+            if let Some(migration) = decoder.decode_migration(&ctx) {
+                match build_migration(migration, &ctx, &position, timestamp, slot, &signature).await
+                {
+                    Ok(migration) => migrations.push(migration),
+                    Err(err) => skipped.record(&err),
+                }
+            }
+            if let Some(route) = decoder.decode_route(&ctx) {
+                routed.insert(inner.index);
+                match build_route(route, &ctx, timestamp, slot, &signature).await {
+                    Ok(route) => routes.push(route),
+                    Err(err) => skipped.record(&err),
+                }
+            }
+        }
     }
 
-    let transaction = trx.transaction.clone();
-    let ui = match transaction {
-        solana_transaction_status::EncodedTransaction::Json(ui_transaction) => ui_transaction,
-        _ => return None,
+    for (idx, inst) in msg.instructions.into_iter().enumerate() {
+        let decoder = match all_addresses
+            .get(inst.program_id_index as usize)
+            .and_then(|program_add| DECODERS.get(program_add))
+        {
+            Some(decoder) => decoder,
+            None => continue,
+        };
+        let decoded_data = match bs58::decode(inst.data.clone()).into_vec() {
+            Ok(data) => data,
+            Err(_) => {
+                skipped.record(&DecodeError::InvalidInstructionData { index: idx as u32 });
+                continue;
+            }
+        };
+
+        let ctx = DecodeContext {
+            data: &decoded_data,
+            input_accounts: prepare_input_accounts(&inst.accounts, &all_addresses),
+            accounts: &all_addresses,
+            signers,
+            pre_token_balances: &pre_token_balances_vec,
+            post_token_balances: &post_token_balances_vec,
+            pre_balances: &pre_balances,
+            post_balances: &post_balances,
+            inner_instructions: inners
+                .iter()
+                .find(|inner| inner.index == idx as u32)
+                .map(|inner| inner.instructions.as_slice())
+                .unwrap_or(&[]),
+            logs: logs.get(&(idx as u32, None)).copied().unwrap_or(&[]),
+            is_inner: false,
+        };
+
+        let position = InstructionPosition {
+            index: idx as u32,
+            inner_index: None,
+            stack_height: Some(1),
+            outer_program: None,
+            invoking_program: None,
+        };
+        match build_trade_data(decoder, &ctx, &position, timestamp, slot, &signature, fee).await {
+            Ok(legs) => trades.extend(legs.into_iter().map(|trade| (idx as u32, None, trade))),
+            Err(err) => skipped.record(&err),
+        }
+        if let Some(liquidity) = decoder.decode_liquidity(&ctx) {
+            match build_liquidity_event(
+                liquidity, &ctx, &position, timestamp, slot, &signature, fee,
+            )
+            .await
+            {
+                Ok(event) => liquidity_events.push(event),
+                Err(err) => skipped.record(&err),
+            }
+        }
+        if let Some(pool) = decoder.decode_pool_creation(&ctx) {
+            match build_pool_created(pool, &ctx, &position, timestamp, slot, &signature).await {
+                Ok(pool) => pools_created.push(pool),
+                Err(err) => skipped.record(&err),
+            }
+        }
+        if let Some(migration) = decoder.decode_migration(&ctx) {
+            match build_migration(migration, &ctx, &position, timestamp, slot, &signature).await {
+                Ok(migration) => migrations.push(migration),
+                Err(err) => skipped.record(&err),
+            }
+        }
+        if let Some(route) = decoder.decode_route(&ctx) {
+            routed.insert(idx as u32);
+            match build_route(route, &ctx, timestamp, slot, &signature).await {
+                Ok(route) => routes.push(route),
+                Err(err) => skipped.record(&err),
+            }
+        }
+    }
+
+    // execution order: each top-level instruction, then its inner ones
+    trades.sort_by_key(|(top_idx, inner_idx, _)| (*top_idx, *inner_idx));
+
+    // several rows under one top-level instruction are linked into a route
+    // when a router made them or when each leg spends what the previous one
+    // bought; unrelated swaps batched into one instruction stay apart
+    let mut legs_per_instruction: HashMap<u32, Vec<&TradeData>> = HashMap::new();
+    for (top_idx, _, trade) in trades.iter() {
+        legs_per_instruction.entry(*top_idx).or_default().push(trade);
+    }
+    let linked: HashSet<u32> = legs_per_instruction
+        .into_iter()
+        .filter(|(top_idx, legs)| legs.len() > 1 && (routed.contains(top_idx) || legs_chain(legs)))
+        .map(|(top_idx, _)| top_idx)
+        .collect();
+
+    let trades = trades
+        .into_iter()
+        .map(|(top_idx, _, mut trade)| {
+            if linked.contains(&top_idx) {
+                trade.route_id = format!("{}:{}", signature, top_idx);
+            }
+            trade.priority_fee_lamports = compute_budget.priority_fee_lamports();
+            trade.cu_limit = compute_budget.cu_limit;
+            trade.cu_price_micro_lamports = compute_budget.cu_price_micro_lamports;
+            trade.cu_consumed = cu_consumed;
+            trade.jito_tip_lamports = jito_tip;
+            trade
+        })
+        .collect();
+
+    Ok(DecodedTx {
+        trades,
+        liquidity_events,
+        pools_created,
+        migrations,
+        routes,
+    })
+}
+
+/// Whether each leg, in execution order, spends the mint the previous one
+/// bought.
+fn legs_chain(legs: &[&TradeData]) -> bool {
+    legs.windows(2).all(|pair| {
+        !pair[0].output_mint.is_empty() && pair[0].output_mint == pair[1].input_mint
+    })
+}
+
+/// Decodes the swaps a failed transaction attempted, along with its error.
+/// Empty if the transaction didn't fail.
+pub async fn process_failed_tx(
+    trx: EncodedTransactionWithStatusMeta,
+    slot: u64,
+    timestamp: i64,
+) -> Result<Vec<FailedSwap>, DecodeError> {
+    let err = match trx.meta.as_ref().and_then(|meta| meta.err.clone()) {
+        Some(err) => err,
+        None => return Ok(vec![]),
     };
+    let UnpackedTx {
+        signature,
+        msg,
+        meta: trx_meta,
+        all_addresses,
+        pre_token_balances,
+        post_token_balances,
+    } = unpack_tx(trx)?;
 
-    let signature = ui.signatures[0].clone();
+    let signers = signers(&msg, &all_addresses);
 
-    let msg = match ui.message {
-        solana_transaction_status::UiMessage::Raw(raw_msg) => raw_msg,
-        _ => return None,
+    let (failed_instruction_index, error_code) = match &err {
+        TransactionError::InstructionError(idx, InstructionError::Custom(code)) => {
+            (*idx as i32, *code as i64)
+        }
+        TransactionError::InstructionError(idx, _) => (*idx as i32, -1),
+        _ => (-1, -1),
     };
 
-    let accounts = msg.account_keys;
+    // inner instructions are only recorded up to the failure, if at all
+    let inners: Vec<InnerInstructions> = trx_meta
+        .inner_instructions
+        .clone()
+        .unwrap_or(vec![])
+        .iter()
+        .map(decode_inner_instructions)
+        .collect();
+    let log_messages = trx_meta.log_messages.clone().unwrap_or(vec![]);
+    let invocations = split_invocations(&log_messages);
+    let logs = instruction_logs(&msg.instructions, &inners, &all_addresses, &invocations);
 
-    let mut all_addresses = accounts.clone();
+    let block_date = convert_to_date(timestamp).await;
+    let mut failed_swaps: Vec<FailedSwap> = vec![];
 
-    let loaded_addresses = trx_meta.loaded_addresses.clone();
+    for (idx, inst) in msg.instructions.iter().enumerate() {
+        let decoder_at = |program_id_index: usize| {
+            all_addresses
+                .get(program_id_index)
+                .and_then(|program_add| DECODERS.get(program_add))
+        };
+        let outer_program = all_addresses.get(inst.program_id_index as usize);
+        let inner = inners.iter().find(|inner| inner.index == idx as u32);
+        let data = bs58::decode(inst.data.clone()).into_vec().unwrap_or_default();
 
-    if loaded_addresses.is_some() {
-        let loaded_addresses = loaded_addresses.unwrap();
-        loaded_addresses.writable.iter().for_each(|add| {
-            all_addresses.push(add.clone());
-        });
-        loaded_addresses.readonly.iter().for_each(|add| {
-            all_addresses.push(add.clone());
-        });
+        // the instruction itself, then whatever it invoked before failing
+        let mut attempts: Vec<(&dyn DexDecoder, DecodeContext, Option<usize>)> = vec![];
+        if let Some(decoder) = decoder_at(inst.program_id_index as usize) {
+            let ctx = DecodeContext {
+                data: &data,
+                input_accounts: prepare_input_accounts(&inst.accounts, &all_addresses),
+                accounts: &all_addresses,
+                signers,
+                pre_token_balances: &pre_token_balances,
+                post_token_balances: &post_token_balances,
+                pre_balances: &trx_meta.pre_balances,
+                post_balances: &trx_meta.post_balances,
+                inner_instructions: inner
+                    .map(|inner| inner.instructions.as_slice())
+                    .unwrap_or(&[]),
+                logs: logs.get(&(idx as u32, None)).copied().unwrap_or(&[]),
+                is_inner: false,
+            };
+            attempts.push((decoder, ctx, None));
+        }
+        for inner in inner.iter() {
+            for (inner_idx, inner_inst) in inner.instructions.iter().enumerate() {
+                let decoder = match decoder_at(inner_inst.program_id_index as usize) {
+                    Some(decoder) => decoder,
+                    None => continue,
+                };
+                let ctx = DecodeContext {
+                    data: &inner_inst.data,
+                    input_accounts: prepare_input_accounts(&inner_inst.accounts, &all_addresses),
+                    accounts: &all_addresses,
+                    signers,
+                    pre_token_balances: &pre_token_balances,
+                    post_token_balances: &post_token_balances,
+                    pre_balances: &trx_meta.pre_balances,
+                    post_balances: &trx_meta.post_balances,
+                    inner_instructions: nested_instructions(&inner.instructions, inner_idx),
+                    logs: logs.get(&(idx as u32, Some(inner_idx))).copied().unwrap_or(&[]),
+                    is_inner: true,
+                };
+                attempts.push((decoder, ctx, Some(inner_idx)));
+            }
+        }
+
+        for (decoder, ctx, inner_idx) in attempts {
+            // aggregators are recorded with no pool
+            let legs = decoder
+                .decode_legs(&ctx)
+                .into_iter()
+                .map(|td| (td.amm, td.dapp_address, td.name));
+            let routes = decoder
+                .decode_route(&ctx)
+                .map(|route| (String::new(), route.dapp_address, route.name));
+            for (pool_address, program, instruction_type) in legs.chain(routes) {
+                failed_swaps.push(FailedSwap {
+                    block_date: block_date.clone(),
+                    block_time: timestamp,
+                    block_slot: slot,
+                    signature: signature.clone(),
+                    signer: all_addresses.first().cloned().unwrap_or_default(),
+                    pool_address,
+                    program,
+                    instruction_type,
+                    is_inner_instruction: inner_idx.is_some(),
+                    instruction_index: idx as u32,
+                    inner_instruction_index: inner_idx.unwrap_or(0) as u32,
+                    outer_program: match inner_idx {
+                        Some(_) => outer_program.cloned().unwrap_or_default(),
+                        None => "".to_string(),
+                    },
+                    txn_fee_lamports: trx_meta.fee,
+                    failed_instruction_index,
+                    error_code,
+                    error: err.to_string(),
+                });
+            }
+        }
     }
 
-    let pre_balances = trx_meta.pre_balances;
-    let post_balances = trx_meta.post_balances;
+    Ok(failed_swaps)
+}
+
+/// Where an instruction sits in the transaction.
+struct InstructionPosition<'a> {
+    /// Index of the top-level instruction, the instruction's own or the one
+    /// it was invoked under.
+    index: u32,
+    /// Position among the top-level instruction's inner instructions, `None`
+    /// for top-level instructions.
+    inner_index: Option<u32>,
+    /// 1 for top-level instructions, `None` when the block predates stack
+    /// heights.
+    stack_height: Option<u32>,
+    /// Program of the top-level instruction, for inner instructions.
+    outer_program: Option<&'a String>,
+    /// Program that made the CPI, for inner instructions.
+    invoking_program: Option<&'a String>,
+}
+
+/// Accounts that signed the transaction: the leading static account keys.
+fn signers<'a>(msg: &UiRawMessage, all_addresses: &'a [String]) -> &'a [String] {
+    let count = msg.header.num_required_signatures as usize;
+    &all_addresses[..count.min(all_addresses.len())]
+}
+
+/// The parts of a transaction the decoding passes work on.
+struct UnpackedTx {
+    signature: String,
+    msg: UiRawMessage,
+    meta: UiTransactionStatusMeta,
+    /// Static account keys followed by the loaded writable and readonly
+    /// addresses, the order instructions index into.
+    all_addresses: Vec<String>,
+    pre_token_balances: Vec<TokenBalance>,
+    post_token_balances: Vec<TokenBalance>,
+}
+
+fn unpack_tx(trx: EncodedTransactionWithStatusMeta) -> Result<UnpackedTx, DecodeError> {
+    let mut trx_meta = trx.meta.ok_or(DecodeError::MissingMeta)?;
+
+    let RawTransaction {
+        signature,
+        message: msg,
+        all_addresses,
+    } = to_raw_transaction(trx.transaction, &mut trx_meta)
+        .ok_or(DecodeError::UndecodableTransaction)?;
+
     let pre_token_balances = trx_meta
         .pre_token_balances
-        .expect("Pre token balances not found");
-    // convert to Vec<TokenBalance>
-    let mut pre_token_balances_vec: Vec<TokenBalance> = vec![];
-    for (idx, balance) in pre_token_balances.iter().enumerate() {
-        let token_balance = TokenBalance {
-            account_index: idx as u32,
-            address: all_addresses
-                .get(balance.account_index as usize)
-                .unwrap_or(&"".to_string())
-                .to_string(),
-            mint: balance.mint.clone(),
-            ui_token_amount: UiTokenAmount {
-                ui_amount: balance.ui_token_amount.ui_amount.unwrap_or(0.0),
-                decimals: balance.ui_token_amount.decimals as u32,
-                amount: balance.ui_token_amount.amount.clone(),
-                ui_amount_string: balance.ui_token_amount.ui_amount_string.clone(),
-            },
-            owner: balance.owner.clone().unwrap(),
-            program_id: balance.program_id.clone().unwrap(),
-        };
-        pre_token_balances_vec.push(token_balance);
-    }
-
+        .clone()
+        .ok_or(DecodeError::MissingTokenBalances)?;
     let post_token_balances = trx_meta
         .post_token_balances
-        .expect("Post token balances not found");
-    let mut post_token_balances_vec: Vec<TokenBalance> = vec![];
-    for (idx, balance) in post_token_balances.iter().enumerate() {
+        .clone()
+        .ok_or(DecodeError::MissingTokenBalances)?;
+
+    Ok(UnpackedTx {
+        signature,
+        msg,
+        pre_token_balances: convert_token_balances(&pre_token_balances, &all_addresses),
+        post_token_balances: convert_token_balances(&post_token_balances, &all_addresses),
+        meta: trx_meta,
+        all_addresses,
+    })
+}
+
+fn convert_token_balances(
+    balances: &[UiTransactionTokenBalance],
+    all_addresses: &[String],
+) -> Vec<TokenBalance> {
+    let mut token_balances: Vec<TokenBalance> = vec![];
+    for (idx, balance) in balances.iter().enumerate() {
         let token_balance = TokenBalance {
             account_index: idx as u32,
             address: all_addresses
@@ -106,352 +515,457 @@ pub async fn process_tx(
                 amount: balance.ui_token_amount.amount.clone(),
                 ui_amount_string: balance.ui_token_amount.ui_amount_string.clone(),
             },
-            owner: balance.owner.clone().unwrap(),
-            program_id: balance.program_id.clone().unwrap(),
+            owner: balance.owner.clone().unwrap_or_else(String::new),
+            program_id: balance.program_id.clone().unwrap_or_else(String::new),
         };
-        post_token_balances_vec.push(token_balance);
+        token_balances.push(token_balance);
     }
+    token_balances
+}
 
-    let mut trades: Vec<TradeData> = vec![];
-
-    let fee = trx_meta.fee;
+fn decode_inner_instructions(inner: &UiInnerInstructions) -> InnerInstructions {
+    InnerInstructions {
+        index: inner.index as u32,
+        instructions: inner
+            .instructions
+            .iter()
+            .filter_map(|inner_inst| match inner_inst {
+                UiInstruction::Compiled(compiled) => Some(InnerInstruction {
+                    program_id_index: compiled.program_id_index as u32,
+                    accounts: compiled.accounts.clone(),
+                    data: bs58::decode(compiled.data.clone())
+                        .into_vec()
+                        .unwrap_or_default(),
+                    stack_height: compiled.stack_height,
+                }),
+                UiInstruction::Parsed(_) => None,
+            })
+            .collect(),
+    }
+}
 
-    let inners = trx_meta.inner_instructions.clone().unwrap_or(vec![]);
+/// Logs of every instruction, keyed by the top-level instruction index and the
+/// position among its inner instructions (`None` for the top-level one).
+fn instruction_logs<'a>(
+    instructions: &[UiCompiledInstruction],
+    inners: &[InnerInstructions],
+    accounts: &[String],
+    invocations: &'a [InvocationLogs],
+) -> HashMap<(u32, Option<usize>), &'a [String]> {
+    let program_at = |program_id_index: usize| {
+        accounts
+            .get(program_id_index)
+            .map(String::as_str)
+            .unwrap_or_default()
+    };
 
-    // iterate over inners
-    for inner in inners.iter() {
-        for (idx, inner_inst) in inner.instructions.iter().enumerate() {
-            if let solana_transaction_status::UiInstruction::Compiled(compiled) = inner_inst {
-                let program_data = match bs58::decode(compiled.data.clone()).into_vec() {
-                    Ok(data) => data,
-                    Err(_) => continue,
-                };
-                let program_add = all_addresses.get(compiled.program_id_index as usize)?;
-                
-                match program_add.as_str() {
-                    RAYDIUM_PROGRAM_ID => {
-                        let (base_add, quote_add) = match compiled.accounts.len() {
-                            17 => {
-                                let base_add = all_addresses.get(compiled.accounts[4] as usize)?.clone();
-                                let quote_add = all_addresses.get(compiled.accounts[5] as usize)?.clone();
-                                (base_add, quote_add)
-                            },
-                            18 => {
-                                let base_add = all_addresses.get(compiled.accounts[5] as usize)?.clone();
-                                let quote_add = all_addresses.get(compiled.accounts[6] as usize)?.clone();
-                                (base_add, quote_add)
-                            },
-                            _ => {
-                                continue;
-                            }
-                        };
-                        
-                        if let Some(trade) = build_trade_data(
-                            program_add,
-                            &program_data,
-                            &compiled.accounts,
-                            &all_addresses,
-                            &pre_token_balances_vec,
-                            &post_token_balances_vec,
-                            &base_add,
-                            &quote_add,
-                            // &inners
-                            //     .first()
-                            //     .expect("Inner instructions not found")
-                            //     .instructions,
-                            timestamp,
-                            slot,
-                            &signature,
-                            idx,
-                            // &inners,
-                            &pre_balances,
-                            &post_balances,
-                            fee,
-                        ).await {
-                            trades.push(trade);
-                        }
-                    },
-                    ORCA_PROGRAM_ID => {
-                        if let Some(trade) = build_trade_data(
-                            program_add,
-                            &program_data,
-                            &compiled.accounts,
-                            &all_addresses,
-                            &pre_token_balances_vec,
-                            &post_token_balances_vec,
-                            &"".to_string(),
-                            &"".to_string(),
-                            // &inners
-                            //     .first()
-                            //     .expect("Inner instructions not found")
-                            //     .instructions,
-                            timestamp,
-                            slot,
-                            &signature,
-                            idx,
-                            // &inners,
-                            &pre_balances,
-                            &post_balances,
-                            fee,
-                        ).await {
-                            trades.push(trade);
-                        }
-                    },
-                    METEORA_PROGRAM_ID => {
-                        let base_add = all_addresses.get(6)?.clone();
-                        let quote_add = all_addresses.get(7)?.clone();
-                        if let Some(trade) = build_trade_data(
-                            program_add,
-                            &program_data,
-                            &compiled.accounts,
-                            &all_addresses,
-                            &pre_token_balances_vec,
-                            &post_token_balances_vec,
-                            &base_add,
-                            &quote_add,
-                            // &inners
-                            //     .first()
-                            //     .expect("Inner instructions not found")
-                            //     .instructions,
-                            timestamp,
-                            slot,
-                            &signature,
-                            idx,
-                            // &inners,
-                            &pre_balances,
-                            &post_balances,
-                            fee,
-                        ).await {
-                            trades.push(trade);
-                        }
-                    },
-                    METEORA_DLMM_PROGRAM_ID => {
-                        if let Some(trade) = build_trade_data(
-                            program_add,
-                            &program_data,
-                            &compiled.accounts,
-                            &all_addresses,
-                            &pre_token_balances_vec,
-                            &post_token_balances_vec,
-                            &"".to_string(),
-                            &"".to_string(),
-                            // &inners
-                            //     .first()
-                            //     .expect("Inner instructions not found")
-                            //     .instructions,
-                            timestamp,
-                            slot,
-                            &signature,
-                            idx,
-                            // &inners,
-                            &pre_balances,
-                            &post_balances,
-                            fee,
-                        ).await {
-                            trades.push(trade);
-                        }
-                    }
-                    // if not found - check inner instructions
-                    _ => {                        
-                    }
-                }
+    // instructions in execution order: each top-level one, then its inner ones
+    let mut keys = vec![];
+    let mut program_ids = vec![];
+    for (idx, inst) in instructions.iter().enumerate() {
+        keys.push((idx as u32, None));
+        program_ids.push(program_at(inst.program_id_index as usize));
+        if let Some(inner) = inners.iter().find(|inner| inner.index == idx as u32) {
+            for (position, inner_inst) in inner.instructions.iter().enumerate() {
+                keys.push((idx as u32, Some(position)));
+                program_ids.push(program_at(inner_inst.program_id_index as usize));
             }
         }
     }
-    
-    for (idx, inst) in msg.instructions.into_iter().enumerate() {
 
-        let trx_meta_inner = trx_meta.inner_instructions.clone().unwrap_or(vec![]);
-
-        let decoded_data = bs58::decode(inst.data.clone()).into_vec().unwrap();
-        
-        let main_program = all_addresses.get(inst.program_id_index as usize).unwrap();
-        match main_program.as_str() {
-            RAYDIUM_PROGRAM_ID => {
-                // standard raydium - srmq add
-                if let Some(pos) = inst
-                    .accounts
-                    .iter()
-                    .position(|&ix| all_addresses[ix as usize] == SERUM_ADD)
-                {
-                    // no extra checks, just do pos-2 and pos-1 as before
-                    let base_add = all_addresses
-                        .get(inst.accounts[pos - 2] as usize)
-                        .expect("Base account not found")
-                        .clone();
-
-                    let quote_add = all_addresses
-                        .get(inst.accounts[pos - 1] as usize)
-                        .expect("Quote account not found")
-                        .clone();
-
-                    if let Some(trade) = build_trade_data(
-                        main_program,
-                        &decoded_data,
-                        &inst.accounts,
-                        &all_addresses,
-                        &pre_token_balances_vec,
-                        &post_token_balances_vec,
-                        &base_add,
-                        &quote_add,
-                        // &inners
-                        //     .first()
-                        //     .expect("Inner instructions not found")
-                        //     .instructions,
-                        timestamp,
-                        slot,
-                        &signature,
-                        idx,
-                        // &inners,
-                        &pre_balances,
-                        &post_balances,
-                        fee,
-                    ).await {
-                        trades.push(trade);
-                    }
-                }
-            }
-            ORCA_PROGRAM_ID => {
-                if let Some(trade) = build_trade_data(
-                    main_program,
-                    &decoded_data,
-                    &inst.accounts,
-                    &all_addresses,
-                    &pre_token_balances_vec,
-                    &post_token_balances_vec,
-                    &"".to_string(),
-                    &"".to_string(),
-                    // &inners
-                    //     .first()
-                    //     .expect("Inner instructions not found")
-                    //     .instructions,
-                    timestamp,
-                    slot,
-                    &signature,
-                    idx,
-                    // &inners,
-                    &pre_balances,
-                    &post_balances,
-                    fee,
-                ).await {
-                    trades.push(trade);
-                }
-            },
-            METEORA_PROGRAM_ID => {
-                let base_add = all_addresses.get(6)?.clone();
-                let quote_add = all_addresses.get(7)?.clone();
-                if let Some(trade) = build_trade_data(
-                    main_program,
-                    &decoded_data,
-                    &inst.accounts,
-                    &all_addresses,
-                    &pre_token_balances_vec,
-                    &post_token_balances_vec,
-                    &base_add,
-                    &quote_add,
-                    timestamp,
-                    slot,
-                    &signature,
-                    idx,
-                    &pre_balances,
-                    &post_balances,
-                    fee,
-                ).await {
-                    trades.push(trade);
-                }
-            },
-            METEORA_DLMM_PROGRAM_ID => {
-                if let Some(trade) = build_trade_data(
-                    main_program,
-                    &decoded_data,
-                    &inst.accounts,
-                    &all_addresses,
-                    &pre_token_balances_vec,
-                    &post_token_balances_vec,
-                    &"".to_string(),
-                    &"".to_string(),
-                    timestamp,
-                    slot,
-                    &signature,
-                    idx,
-                    &pre_balances,
-                    &post_balances,
-                    fee,
-                ).await {
-                    trades.push(trade);
-                }
-            }
-            // if not found - check inner instructions
-            _ => {
-                // something's fucked up here - inner is repeated 4 times (clone)
-                
-            }
-        };
-    }
-    Some(trades)
+    keys.into_iter()
+        .zip(match_invocations(&program_ids, invocations))
+        .collect()
+}
+
+/// Program of the closest preceding inner instruction one level up the stack
+/// from the one at `position`. `None` when the caller is the top-level
+/// instruction or stack heights aren't available.
+fn invoking_program<'a>(
+    instructions: &[InnerInstruction],
+    position: usize,
+    accounts: &'a [String],
+) -> Option<&'a String> {
+    let height = instructions.get(position)?.stack_height?;
+    instructions[..position]
+        .iter()
+        .rev()
+        .find(|inst| inst.stack_height == Some(height - 1))
+        .and_then(|inst| accounts.get(inst.program_id_index as usize))
+}
+
+/// Inner instructions executed underneath the inner instruction at `position`:
+/// everything that follows it with a greater stack height. Without stack
+/// heights (older blocks) nesting can't be told apart, so nothing is returned.
+fn nested_instructions(instructions: &[InnerInstruction], position: usize) -> &[InnerInstruction] {
+    let height = match instructions.get(position).and_then(|inst| inst.stack_height) {
+        Some(height) => height,
+        None => return &[],
+    };
+    let rest = &instructions[position + 1..];
+    let end = rest
+        .iter()
+        .position(|inst| inst.stack_height.is_none_or(|h| h <= height))
+        .unwrap_or(rest.len());
+    &rest[..end]
 }
 
+/// Builds one row per leg the decoder finds in the instruction.
+///
+/// Swaps invoked through CPI get the top-level instruction's program as
+/// `outer_program` and the venue as `inner_program`. A leg whose mints or
+/// amounts can't be resolved fails the whole instruction.
 async fn build_trade_data(
-    program: &String,
-    decoded_data: &Vec<u8>,
-    inst_accounts: &Vec<u8>,
-    accounts: &Vec<String>,
-    pre_token_balances_vec: &Vec<TokenBalance>,
-    post_token_balances_vec: &Vec<TokenBalance>,
-    base_add: &String,
-    quote_add: &String,
+    decoder: &dyn DexDecoder,
+    ctx: &DecodeContext<'_>,
+    position: &InstructionPosition<'_>,
     timestamp: i64,
     slot: u64,
     signature: &String,
-    idx: usize,
-    pre_balances: &Vec<u64>,
-    post_balances: &Vec<u64>,
     fee: u64,
-) -> Option<TradeData> {
-    let trade_data = get_trade_instruction(
-        program,
-        decoded_data,
-        inst_accounts,
-        accounts,
-        pre_token_balances_vec,
-        post_token_balances_vec,
-        &"".to_string(),
-        false,
-        0,
-        base_add,
-        quote_add,
-    );
-    // 2. If there's a return, build the TradeData struct
-    if let Some(td) = trade_data {
-        let td_name = td.name;
-        let td_address = td.dapp_address;
-
-        let trade = TradeData {
+) -> Result<Vec<TradeData>, DecodeError> {
+    let signer = ctx.accounts.first().ok_or(DecodeError::MissingSigner)?;
+    let mut trades = vec![];
+
+    for td in decoder.decode_legs(ctx) {
+        let (base_mint, quote_mint) = decoder
+            .resolve_mints(&td, ctx)
+            .ok_or_else(|| DecodeError::UnresolvedMints {
+                pool: td.amm.clone(),
+            })?;
+
+        // decoder-reported amounts, then the instruction's own token transfers,
+        // then the vault's balance diff over the whole transaction
+        let resolve_amount =
+            |amount: Option<TokenAmount>, transfer_fee: Option<u64>, vault: &str| {
+                let transfer = transfer_amount(vault, ctx);
+                let transfer_fee = transfer_fee
+                    .or(transfer.map(|(_, fee)| fee))
+                    .unwrap_or(0);
+                let amount = amount
+                    .or(transfer.map(|(amount, _)| amount))
+                    .or_else(|| token_balance_change(vault, ctx))?;
+                Some((amount, transfer_fee))
+            };
+        let (base_amount, base_transfer_fee, quote_amount, quote_transfer_fee) = match (
+            resolve_amount(td.amount_a, td.transfer_fee_a, &td.vault_a),
+            resolve_amount(td.amount_b, td.transfer_fee_b, &td.vault_b),
+        ) {
+            (Some((base_amount, base_fee)), Some((quote_amount, quote_fee))) => {
+                (base_amount, base_fee, quote_amount, quote_fee)
+            }
+            _ => {
+                return Err(DecodeError::UnresolvedAmounts {
+                    pool: td.amm.clone(),
+                })
+            }
+        };
+        let direction = trade_direction(&base_mint, base_amount, &quote_mint, quote_amount);
+        let (input_mint, output_mint, amount_in, amount_out, side) = match direction {
+            Some(direction) => (
+                direction.input_mint.to_string(),
+                direction.output_mint.to_string(),
+                direction.amount_in.ui_amount(),
+                direction.amount_out.ui_amount(),
+                direction.side.to_string(),
+            ),
+            None => Default::default(),
+        };
+
+        let (outer_program, inner_program) = match position.outer_program {
+            Some(outer_program) => (outer_program.clone(), td.dapp_address),
+            None => (td.dapp_address, "".to_string()),
+        };
+        let trader_accounts = trader_accounts(&[&td.vault_a, &td.vault_b], ctx);
+
+        trades.push(TradeData {
             block_date: convert_to_date(timestamp).await,
             tx_id: bs58::encode(signature).into_string(),
             block_slot: slot,
             block_time: timestamp,
             signature: signature.to_string(),
-            signer: accounts.get(0).unwrap().to_string(),
+            signer: signer.to_string(),
             pool_address: td.amm,
-            base_mint: get_mint(&td.vault_a, post_token_balances_vec).await.ok_or(format!("Base mint not found for signature {}, vault: {}", signature, td.vault_a)).unwrap(),
-            quote_mint: get_mint(&td.vault_b, post_token_balances_vec).await.ok_or(format!("Quote mint not found for signature {}, vault: {}", signature, td.vault_b)).unwrap(),
-            base_amount: get_amount(&td.vault_a, pre_token_balances_vec, post_token_balances_vec).await,
-            quote_amount: get_amount(&td.vault_b, pre_token_balances_vec, post_token_balances_vec).await,
+            base_mint,
+            quote_mint,
+            base_amount: base_amount.ui_amount(),
+            quote_amount: quote_amount.ui_amount(),
+            base_amount_raw: base_amount.raw,
+            quote_amount_raw: quote_amount.raw,
+            base_decimals: base_amount.decimals,
+            quote_decimals: quote_amount.decimals,
+            base_amount_gross: gross_amount(base_amount, base_transfer_fee),
+            quote_amount_gross: gross_amount(quote_amount, quote_transfer_fee),
+            base_transfer_fee: ui_amount(base_transfer_fee, base_amount.decimals),
+            quote_transfer_fee: ui_amount(quote_transfer_fee, quote_amount.decimals),
+            swap_fee: td.fee.unwrap_or(0.0),
+            post_swap_price: td.post_price.unwrap_or(0.0),
+            base_reserve: td.reserve_a.unwrap_or(0.0),
+            quote_reserve: td.reserve_b.unwrap_or(0.0),
+            input_mint,
+            output_mint,
+            amount_in,
+            amount_out,
+            side,
             base_vault: td.vault_a,
             quote_vault: td.vault_b,
-            is_inner_instruction: false,
-            instruction_index: idx as u32,
-            instruction_type: td_name.clone(),
-            inner_instruction_index: 0,
-            outer_program: td_address.clone(),
-            inner_program: "".to_string(),
+            is_inner_instruction: position.inner_index.is_some(),
+            instruction_index: position.index,
+            instruction_type: td.name,
+            inner_instruction_index: position.inner_index.unwrap_or(0),
+            stack_height: position.stack_height.unwrap_or(0),
+            outer_program,
+            inner_program,
+            invoking_program: position
+                .invoking_program
+                .cloned()
+                .unwrap_or_default(),
+            route_id: "".to_string(),
             txn_fee_lamports: fee,
-            signer_lamports_change: get_signer_balance_change(pre_balances, post_balances).await,
-        };
+            priority_fee_lamports: 0,
+            cu_limit: 0,
+            cu_price_micro_lamports: 0,
+            cu_consumed: 0,
+            jito_tip_lamports: 0,
+            trader_accounts,
+            signer_lamports_change: get_signer_balance_change(
+                signer,
+                ctx.accounts,
+                ctx.pre_balances,
+                ctx.post_balances,
+            )
+            .await,
+        });
+    }
 
-        Some(trade)
-    } else {
-        None
+    Ok(trades)
+}
+
+/// Builds the row of a liquidity deposit or withdrawal. Amounts resolve like
+/// swap legs: decoder, then the instruction's transfers, then the vault's
+/// balance diff. The missing side of a single-sided deposit is left empty.
+async fn build_liquidity_event(
+    liquidity: LiquidityInstruction,
+    ctx: &DecodeContext<'_>,
+    position: &InstructionPosition<'_>,
+    timestamp: i64,
+    slot: u64,
+    signature: &String,
+    fee: u64,
+) -> Result<LiquidityEvent, DecodeError> {
+    let signer = ctx.accounts.first().ok_or(DecodeError::MissingSigner)?;
+
+    let resolve_side = |mint: &str, vault: &str, amount: Option<TokenAmount>| {
+        if vault.is_empty() {
+            return Ok((String::new(), TokenAmount { raw: 0, decimals: 0 }));
+        }
+        let mint = resolve_mint(mint, vault, ctx).ok_or_else(|| DecodeError::UnresolvedMints {
+            pool: liquidity.amm.clone(),
+        })?;
+        let amount = amount
+            .or_else(|| transfer_amount(vault, ctx).map(|(amount, _)| amount))
+            .or_else(|| token_balance_change(vault, ctx))
+            .ok_or_else(|| DecodeError::UnresolvedAmounts {
+                pool: liquidity.amm.clone(),
+            })?;
+        Ok((mint, amount))
+    };
+    let (base_mint, base_amount) =
+        resolve_side(&liquidity.mint_a, &liquidity.vault_a, liquidity.amount_a)?;
+    let (quote_mint, quote_amount) =
+        resolve_side(&liquidity.mint_b, &liquidity.vault_b, liquidity.amount_b)?;
+
+    let (outer_program, inner_program) = match position.outer_program {
+        Some(outer_program) => (outer_program.clone(), liquidity.dapp_address),
+        None => (liquidity.dapp_address, "".to_string()),
+    };
+
+    Ok(LiquidityEvent {
+        block_date: convert_to_date(timestamp).await,
+        block_time: timestamp,
+        block_slot: slot,
+        signature: signature.to_string(),
+        signer: signer.to_string(),
+        pool_address: liquidity.amm,
+        position: liquidity.position,
+        action: liquidity.action.as_str().to_string(),
+        instruction_type: liquidity.name,
+        base_mint,
+        quote_mint,
+        base_vault: liquidity.vault_a,
+        quote_vault: liquidity.vault_b,
+        base_amount: base_amount.ui_amount(),
+        quote_amount: quote_amount.ui_amount(),
+        base_amount_raw: base_amount.raw,
+        quote_amount_raw: quote_amount.raw,
+        base_decimals: base_amount.decimals,
+        quote_decimals: quote_amount.decimals,
+        lp_mint: liquidity.lp_mint,
+        lp_amount: liquidity
+            .lp_amount
+            .map(|amount| amount.ui_amount())
+            .unwrap_or(0.0),
+        liquidity: liquidity
+            .liquidity
+            .map(|liquidity| liquidity.to_string())
+            .unwrap_or_default(),
+        is_inner_instruction: position.inner_index.is_some(),
+        instruction_index: position.index,
+        inner_instruction_index: position.inner_index.unwrap_or(0),
+        outer_program,
+        inner_program,
+        txn_fee_lamports: fee,
+    })
+}
+
+/// Builds the row of a newly created pool. The initial deposit resolves like
+/// a liquidity deposit, except that pools which open empty get a reserve of 0
+/// rather than being skipped.
+async fn build_pool_created(
+    pool: PoolCreationInstruction,
+    ctx: &DecodeContext<'_>,
+    position: &InstructionPosition<'_>,
+    timestamp: i64,
+    slot: u64,
+    signature: &String,
+) -> Result<PoolCreated, DecodeError> {
+    let resolve_side = |mint: &str, vault: &str, amount: Option<TokenAmount>| {
+        let mint = resolve_mint(mint, vault, ctx).ok_or_else(|| DecodeError::UnresolvedMints {
+            pool: pool.amm.clone(),
+        })?;
+        let amount = amount
+            .or_else(|| transfer_amount(vault, ctx).map(|(amount, _)| amount))
+            .or_else(|| token_balance_change(vault, ctx))
+            .unwrap_or(TokenAmount {
+                raw: 0,
+                decimals: mint_decimals(&mint, ctx).unwrap_or(0),
+            });
+        Ok((mint, amount))
+    };
+    let (base_mint, base_reserve) = resolve_side(&pool.mint_a, &pool.vault_a, pool.amount_a)?;
+    let (quote_mint, quote_reserve) = resolve_side(&pool.mint_b, &pool.vault_b, pool.amount_b)?;
+
+    let initial_price = pool
+        .initial_price
+        .or_else(|| {
+            (base_reserve.raw > 0 && quote_reserve.raw > 0)
+                .then(|| quote_reserve.ui_amount() / base_reserve.ui_amount())
+        })
+        .unwrap_or(0.0);
+
+    Ok(PoolCreated {
+        block_date: convert_to_date(timestamp).await,
+        block_time: timestamp,
+        block_slot: slot,
+        signature: signature.to_string(),
+        creator: pool.creator,
+        program: pool.dapp_address,
+        instruction_type: pool.name,
+        pool_address: pool.amm,
+        base_mint,
+        quote_mint,
+        base_vault: pool.vault_a,
+        quote_vault: pool.vault_b,
+        lp_mint: pool.lp_mint,
+        base_reserve: base_reserve.ui_amount(),
+        quote_reserve: quote_reserve.ui_amount(),
+        base_reserve_raw: base_reserve.raw,
+        quote_reserve_raw: quote_reserve.raw,
+        base_decimals: base_reserve.decimals,
+        quote_decimals: quote_reserve.decimals,
+        initial_price,
+        is_inner_instruction: position.inner_index.is_some(),
+        instruction_index: position.index,
+        inner_instruction_index: position.inner_index.unwrap_or(0),
+        outer_program: position.outer_program.cloned().unwrap_or_default(),
+    })
+}
+
+/// Builds the route record of an aggregator instruction.
+async fn build_route(
+    route: RouteInstruction,
+    ctx: &DecodeContext<'_>,
+    timestamp: i64,
+    slot: u64,
+    signature: &String,
+) -> Result<RouteTrade, DecodeError> {
+    let signer = ctx.accounts.first().ok_or(DecodeError::MissingSigner)?;
+    let unresolved = || DecodeError::UnresolvedRoute {
+        program: route.dapp_address.clone(),
+        signature: signature.to_string(),
+    };
+    if route.input_mint.is_empty() || route.output_mint.is_empty() {
+        return Err(unresolved());
     }
+    let (amount_in, amount_out) = route.amount_in.zip(route.amount_out).ok_or_else(unresolved)?;
+
+    Ok(RouteTrade {
+        block_date: convert_to_date(timestamp).await,
+        block_time: timestamp,
+        block_slot: slot,
+        signature: signature.to_string(),
+        signer: signer.to_string(),
+        input_mint: route.input_mint,
+        output_mint: route.output_mint,
+        input_amount: amount_in.ui_amount(),
+        output_amount: amount_out.ui_amount(),
+        input_amount_raw: amount_in.raw,
+        output_amount_raw: amount_out.raw,
+        input_decimals: amount_in.decimals,
+        output_decimals: amount_out.decimals,
+        hop_count: route.hop_count,
+        venues: route.venues,
+    })
+}
+
+/// Builds the row of a bonding curve migrating to an AMM.
+async fn build_migration(
+    migration: MigrationInstruction,
+    ctx: &DecodeContext<'_>,
+    position: &InstructionPosition<'_>,
+    timestamp: i64,
+    slot: u64,
+    signature: &String,
+) -> Result<Migration, DecodeError> {
+    let signer = ctx.accounts.first().ok_or(DecodeError::MissingSigner)?;
+    let (sol_amount, token_amount) = migration
+        .sol_amount
+        .zip(migration.token_amount)
+        .ok_or_else(|| DecodeError::UnresolvedAmounts {
+            pool: migration.curve.clone(),
+        })?;
+
+    Ok(Migration {
+        block_date: convert_to_date(timestamp).await,
+        block_time: timestamp,
+        block_slot: slot,
+        signature: signature.to_string(),
+        signer: signer.to_string(),
+        program: migration.dapp_address,
+        instruction_type: migration.name,
+        mint: migration.mint,
+        bonding_curve: migration.curve,
+        destination_program: migration.destination_program,
+        destination_pool: migration.destination_pool,
+        sol_amount: sol_amount.ui_amount(),
+        token_amount: token_amount.ui_amount(),
+        sol_amount_raw: sol_amount.raw,
+        token_amount_raw: token_amount.raw,
+        token_decimals: token_amount.decimals,
+        is_inner_instruction: position.inner_index.is_some(),
+        instruction_index: position.index,
+        inner_instruction_index: position.inner_index.unwrap_or(0),
+    })
+}
+
+/// UI amount a vault's transfer moved before the Token-2022 fee. Amounts are
+/// net of the fee: a vault that was paid got the gross amount less the fee,
+/// one that paid out sent the net amount plus the fee the recipient lost.
+fn gross_amount(amount: TokenAmount, transfer_fee: u64) -> f64 {
+    let raw = if amount.raw > 0 {
+        amount.raw + transfer_fee as i128
+    } else {
+        amount.raw - transfer_fee as i128
+    };
+    TokenAmount { raw, ..amount }.ui_amount()
 }
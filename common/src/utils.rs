@@ -1,15 +1,16 @@
 use crate::global::{OUTPUT_PATH, RPC_CLIENT};
-use crate::models::{MarketDataStruct, TokenBalance, TradeData, Transfer};
+use crate::models::{
+    InnerInstruction, SkippedCounts, TokenBalance, TokenTransfer, TradeData, Transfer,
+    TransferCheckedWithFee,
+};
 use anyhow::Result;
-use avro_rs::types::Record;
-use avro_rs::{Schema, Writer};
+use avro_rs::{to_value, Schema, Writer};
 use borsh::BorshDeserialize;
 use chrono::{DateTime, NaiveDateTime, Utc};
 use csv::WriterBuilder;
+use serde::Serialize;
 use solana_sdk::program_pack::Pack;
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::{bs58, inner_instruction};
-use solana_transaction_status::{UiInnerInstructions, UiInstruction};
 use std::collections::HashMap;
 use std::fs::{create_dir_all, OpenOptions};
 use std::path::Path;
@@ -35,19 +36,230 @@ lazy_static::lazy_static! {
             { "name": "quote_vault", "type": "string" },
             { "name": "base_amount", "type": "double" },
             { "name": "quote_amount", "type": "double" },
+            { "name": "base_amount_raw", "type": "string" },
+            { "name": "quote_amount_raw", "type": "string" },
+            { "name": "base_decimals", "type": "int" },
+            { "name": "quote_decimals", "type": "int" },
+            { "name": "base_amount_gross", "type": "double" },
+            { "name": "quote_amount_gross", "type": "double" },
+            { "name": "base_transfer_fee", "type": "double" },
+            { "name": "quote_transfer_fee", "type": "double" },
+            { "name": "swap_fee", "type": "double" },
+            { "name": "post_swap_price", "type": "double" },
+            { "name": "base_reserve", "type": "double" },
+            { "name": "quote_reserve", "type": "double" },
+            { "name": "input_mint", "type": "string" },
+            { "name": "output_mint", "type": "string" },
+            { "name": "amount_in", "type": "double" },
+            { "name": "amount_out", "type": "double" },
+            { "name": "side", "type": "string" },
             { "name": "is_inner_instruction", "type": "boolean" },
             { "name": "instruction_index", "type": "int" },
             { "name": "instruction_type", "type": "string" },
             { "name": "inner_instruction_index", "type": "int" },
+            { "name": "stack_height", "type": "int" },
             { "name": "outer_program", "type": "string" },
             { "name": "inner_program", "type": "string" },
+            { "name": "invoking_program", "type": "string" },
+            { "name": "route_id", "type": "string" },
             { "name": "txn_fee_lamports", "type": "long" },
+            { "name": "priority_fee_lamports", "type": "long" },
+            { "name": "cu_limit", "type": "long" },
+            { "name": "cu_price_micro_lamports", "type": "long" },
+            { "name": "cu_consumed", "type": "long" },
+            { "name": "jito_tip_lamports", "type": "long" },
+            { "name": "trader_accounts", "type": "string" },
             { "name": "signer_lamports_change", "type": "long" }
         ]
     }
     "#).expect("Failed to parse Avro schema");
 }
 
+lazy_static::lazy_static! {
+    pub static ref ROUTE_AVRO_SCHEMA: Schema = Schema::parse_str(r#"
+    {
+        "type": "record",
+        "name": "RouteTrade",
+        "fields": [
+            { "name": "block_date", "type": "string" },
+            { "name": "block_time", "type": "long" },
+            { "name": "block_slot", "type": "long" },
+            { "name": "signature", "type": "string" },
+            { "name": "signer", "type": "string" },
+            { "name": "input_mint", "type": "string" },
+            { "name": "output_mint", "type": "string" },
+            { "name": "input_amount", "type": "double" },
+            { "name": "output_amount", "type": "double" },
+            { "name": "input_amount_raw", "type": "string" },
+            { "name": "output_amount_raw", "type": "string" },
+            { "name": "input_decimals", "type": "int" },
+            { "name": "output_decimals", "type": "int" },
+            { "name": "hop_count", "type": "int" },
+            { "name": "venues", "type": { "type": "array", "items": "string" } }
+        ]
+    }
+    "#).expect("Failed to parse route Avro schema");
+}
+
+lazy_static::lazy_static! {
+    pub static ref FAILED_AVRO_SCHEMA: Schema = Schema::parse_str(r#"
+    {
+        "type": "record",
+        "name": "FailedSwap",
+        "fields": [
+            { "name": "block_date", "type": "string" },
+            { "name": "block_time", "type": "long" },
+            { "name": "block_slot", "type": "long" },
+            { "name": "signature", "type": "string" },
+            { "name": "signer", "type": "string" },
+            { "name": "pool_address", "type": "string" },
+            { "name": "program", "type": "string" },
+            { "name": "instruction_type", "type": "string" },
+            { "name": "is_inner_instruction", "type": "boolean" },
+            { "name": "instruction_index", "type": "int" },
+            { "name": "inner_instruction_index", "type": "int" },
+            { "name": "outer_program", "type": "string" },
+            { "name": "txn_fee_lamports", "type": "long" },
+            { "name": "failed_instruction_index", "type": "int" },
+            { "name": "error_code", "type": "long" },
+            { "name": "error", "type": "string" }
+        ]
+    }
+    "#).expect("Failed to parse failed swap Avro schema");
+}
+
+lazy_static::lazy_static! {
+    pub static ref LIQUIDITY_AVRO_SCHEMA: Schema = Schema::parse_str(r#"
+    {
+        "type": "record",
+        "name": "LiquidityEvent",
+        "fields": [
+            { "name": "block_date", "type": "string" },
+            { "name": "block_time", "type": "long" },
+            { "name": "block_slot", "type": "long" },
+            { "name": "signature", "type": "string" },
+            { "name": "signer", "type": "string" },
+            { "name": "pool_address", "type": "string" },
+            { "name": "position", "type": "string" },
+            { "name": "action", "type": "string" },
+            { "name": "instruction_type", "type": "string" },
+            { "name": "base_mint", "type": "string" },
+            { "name": "quote_mint", "type": "string" },
+            { "name": "base_vault", "type": "string" },
+            { "name": "quote_vault", "type": "string" },
+            { "name": "base_amount", "type": "double" },
+            { "name": "quote_amount", "type": "double" },
+            { "name": "base_amount_raw", "type": "string" },
+            { "name": "quote_amount_raw", "type": "string" },
+            { "name": "base_decimals", "type": "int" },
+            { "name": "quote_decimals", "type": "int" },
+            { "name": "lp_mint", "type": "string" },
+            { "name": "lp_amount", "type": "double" },
+            { "name": "liquidity", "type": "string" },
+            { "name": "is_inner_instruction", "type": "boolean" },
+            { "name": "instruction_index", "type": "int" },
+            { "name": "inner_instruction_index", "type": "int" },
+            { "name": "outer_program", "type": "string" },
+            { "name": "inner_program", "type": "string" },
+            { "name": "txn_fee_lamports", "type": "long" }
+        ]
+    }
+    "#).expect("Failed to parse liquidity Avro schema");
+}
+
+lazy_static::lazy_static! {
+    pub static ref POOL_AVRO_SCHEMA: Schema = Schema::parse_str(r#"
+    {
+        "type": "record",
+        "name": "PoolCreated",
+        "fields": [
+            { "name": "block_date", "type": "string" },
+            { "name": "block_time", "type": "long" },
+            { "name": "block_slot", "type": "long" },
+            { "name": "signature", "type": "string" },
+            { "name": "creator", "type": "string" },
+            { "name": "program", "type": "string" },
+            { "name": "instruction_type", "type": "string" },
+            { "name": "pool_address", "type": "string" },
+            { "name": "base_mint", "type": "string" },
+            { "name": "quote_mint", "type": "string" },
+            { "name": "base_vault", "type": "string" },
+            { "name": "quote_vault", "type": "string" },
+            { "name": "lp_mint", "type": "string" },
+            { "name": "base_reserve", "type": "double" },
+            { "name": "quote_reserve", "type": "double" },
+            { "name": "base_reserve_raw", "type": "string" },
+            { "name": "quote_reserve_raw", "type": "string" },
+            { "name": "base_decimals", "type": "int" },
+            { "name": "quote_decimals", "type": "int" },
+            { "name": "initial_price", "type": "double" },
+            { "name": "is_inner_instruction", "type": "boolean" },
+            { "name": "instruction_index", "type": "int" },
+            { "name": "inner_instruction_index", "type": "int" },
+            { "name": "outer_program", "type": "string" }
+        ]
+    }
+    "#).expect("Failed to parse pool Avro schema");
+}
+
+lazy_static::lazy_static! {
+    pub static ref MIGRATION_AVRO_SCHEMA: Schema = Schema::parse_str(r#"
+    {
+        "type": "record",
+        "name": "Migration",
+        "fields": [
+            { "name": "block_date", "type": "string" },
+            { "name": "block_time", "type": "long" },
+            { "name": "block_slot", "type": "long" },
+            { "name": "signature", "type": "string" },
+            { "name": "signer", "type": "string" },
+            { "name": "program", "type": "string" },
+            { "name": "instruction_type", "type": "string" },
+            { "name": "mint", "type": "string" },
+            { "name": "bonding_curve", "type": "string" },
+            { "name": "destination_program", "type": "string" },
+            { "name": "destination_pool", "type": "string" },
+            { "name": "sol_amount", "type": "double" },
+            { "name": "token_amount", "type": "double" },
+            { "name": "sol_amount_raw", "type": "string" },
+            { "name": "token_amount_raw", "type": "string" },
+            { "name": "token_decimals", "type": "int" },
+            { "name": "is_inner_instruction", "type": "boolean" },
+            { "name": "instruction_index", "type": "int" },
+            { "name": "inner_instruction_index", "type": "int" }
+        ]
+    }
+    "#).expect("Failed to parse migration Avro schema");
+}
+
+lazy_static::lazy_static! {
+    pub static ref SANDWICH_AVRO_SCHEMA: Schema = Schema::parse_str(r#"
+    {
+        "type": "record",
+        "name": "Sandwich",
+        "fields": [
+            { "name": "block_date", "type": "string" },
+            { "name": "block_time", "type": "long" },
+            { "name": "block_slot", "type": "long" },
+            { "name": "pool_address", "type": "string" },
+            { "name": "attacker", "type": "string" },
+            { "name": "front_run_signature", "type": "string" },
+            { "name": "victim_signature", "type": "string" },
+            { "name": "back_run_signature", "type": "string" },
+            { "name": "victim_signer", "type": "string" },
+            { "name": "victim_side", "type": "string" },
+            { "name": "victim_input_mint", "type": "string" },
+            { "name": "victim_amount_in", "type": "double" },
+            { "name": "quote_mint", "type": "string" },
+            { "name": "attacker_profit", "type": "double" },
+            { "name": "position_mint", "type": "string" },
+            { "name": "position_amount", "type": "double" },
+            { "name": "unmatched_amount", "type": "double" }
+        ]
+    }
+    "#).expect("Failed to parse sandwich Avro schema");
+}
+
 // pub fn get_mint(
 //     address: &String,
 //     token_balances: &Vec<TokenBalance>,
@@ -84,14 +296,10 @@ lazy_static::lazy_static! {
 // }
 
 pub async fn get_mint(address: &String, token_balances: &Vec<TokenBalance>) -> Option<String> {
-    let index = token_balances.iter().position(|r| r.address == *address);
-    match index {
-        None => None,
-        Some(index) => {
-            let mint = token_balances.get(index).unwrap().mint.clone();
-            Some(mint)
-        }
-    }
+    token_balances
+        .iter()
+        .find(|r| r.address == *address)
+        .map(|balance| balance.mint.clone())
 }
 
 // pub fn get_amm_data(amm_address: &String) {
@@ -145,325 +353,156 @@ pub async fn get_mint(address: &String, token_balances: &Vec<TokenBalance>) -> O
 //     return vault_b;
 // }
 
-pub async fn get_signer_balance_change(pre_balances: &Vec<u64>, post_balances: &Vec<u64>) -> i64 {
-    return post_balances[0] as i64 - pre_balances[0] as i64;
+pub(crate) const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub(crate) const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+
+/// Lamport change of the signer over the transaction, looked up by its
+/// position among the account keys. 0 when the balances don't cover it.
+pub async fn get_signer_balance_change(
+    signer: &str,
+    all_addresses: &[String],
+    pre_balances: &[u64],
+    post_balances: &[u64],
+) -> i64 {
+    get_lamports_change(signer, all_addresses, pre_balances, post_balances).unwrap_or(0) as i64
+}
+
+/// Lamport change of `address` over the transaction.
+pub fn get_lamports_change(
+    address: &str,
+    accounts: &[String],
+    pre_balances: &[u64],
+    post_balances: &[u64],
+) -> Option<i128> {
+    let index = accounts.iter().position(|r| r == address)?;
+    Some(*post_balances.get(index)? as i128 - *pre_balances.get(index)? as i128)
 }
 
 pub async fn convert_to_date(ts: i64) -> String {
     let nt = NaiveDateTime::from_timestamp_opt(ts, 0);
-    let dt: DateTime<Utc> = DateTime::from_naive_utc_and_offset(nt.unwrap(), Utc);
+    let dt: DateTime<Utc> = DateTime::from_naive_utc_and_offset(nt.unwrap_or_default(), Utc);
     let res = dt.format("%Y-%m-%d");
     return res.to_string();
 }
 
-pub async fn get_amount(
-    address: &String,
-    pre_token_balances: &Vec<TokenBalance>,
-    post_token_balances: &Vec<TokenBalance>,
-) -> f64{
-    // calculate diff between post_token_balances and pre_token_balances for address
-    let post_amount = post_token_balances.iter().find(|&x| x.address == *address).unwrap();
-    let pre_amount = pre_token_balances.iter().find(|&x| x.address == *address).unwrap();
-    let diff = post_amount.ui_token_amount.ui_amount - pre_amount.ui_token_amount.ui_amount;
-    diff
+/// An SPL Token / Token-2022 `Transfer`, `TransferChecked` or
+/// `TransferCheckedWithFee` instruction.
+struct TokenTransferInstruction<'a> {
+    source: &'a str,
+    destination: &'a str,
+    amount: u64,
+    /// Fee withheld from the destination, if the instruction states it.
+    fee: Option<u64>,
+    is_token_2022: bool,
 }
 
-pub async fn get_amt(
-    address: &String,
-    input_inner_idx: u32,
-    inner_instructions: &Vec<UiInnerInstructions>,
-    accounts: &Vec<String>,
-    post_token_balances: &Vec<TokenBalance>,
-    dapp_address: String,
-    pre_balances: Vec<u64>,
-    post_balances: Vec<u64>,
-) -> f64 {
-    // TODO: this entire function should just get base vault, quote vault, base mint, quote mint and amounts
-    // then just find the amt change for vaults and assign it accordingly to base and quote amounts
-    let mut result: f64 = 0.0;
-
-    let mint = get_mint(address, post_token_balances).await.unwrap();
-
-    if mint == "So11111111111111111111111111111111111111112" {
-        // TODO: REPLACE THIS SHIT
-        // get solana balance change
-        return (get_signer_balance_change(&pre_balances, &post_balances).await as f64)
-            / (u64::pow(10, 9)) as f64;
+/// Reads `inst` as a token transfer, `None` for any other instruction.
+fn parse_token_transfer<'a>(
+    inst: &InnerInstruction,
+    accounts: &'a [String],
+) -> Option<TokenTransferInstruction<'a>> {
+    let program = accounts.get(inst.program_id_index as usize)?;
+    if program != TOKEN_PROGRAM_ID && program != TOKEN_2022_PROGRAM_ID {
+        return None;
     }
-
-    let source_transfer_amt = get_token_transfer(
-        address,
-        input_inner_idx,
-        inner_instructions,
-        accounts,
-        "source".to_string(),
-        dapp_address.clone(),
-        pre_balances.clone(),
-        post_balances.clone(),
-    );
-
-    let destination_transfer_amt = get_token_transfer(
-        address,
-        input_inner_idx,
-        inner_instructions,
-        accounts,
-        "destination".to_string(),
-        dapp_address.clone(),
-        pre_balances.clone(),
-        post_balances.clone(),
-    );
-
-    if source_transfer_amt != 0.0 {
-        result = source_transfer_amt;
-    } else if destination_transfer_amt != 0.0 {
-        result = destination_transfer_amt;
-    }
-
-    if result != 0.0 {
-        // let index = accounts.iter().position(|r| r == address).unwrap();
-        post_token_balances
-            .iter()
-            .filter(|token_balance| token_balance.address == *address)
-            .for_each(|token_balance: &TokenBalance| {
-                let decimals = token_balance.ui_token_amount.clone().decimals;
-                result = result / (u64::pow(10, decimals)) as f64;
-            });
-    }
-
-    -result
+    let is_token_2022 = program == TOKEN_2022_PROGRAM_ID;
+
+    // (source, destination) account positions, amount and fee if stated
+    let (source_idx, destination_idx, amount, fee) = match inst.data.as_slice() {
+        [3, rest @ ..] => Transfer::deserialize(&mut &rest[..])
+            .ok()
+            .map(|transfer| (0, 1, transfer.amount, None)),
+        [12, rest @ ..] => Transfer::deserialize(&mut &rest[..])
+            .ok()
+            .map(|transfer| (0, 2, transfer.amount, None)),
+        // transfer fee extension, TransferCheckedWithFee
+        [26, 1, rest @ ..] if is_token_2022 => {
+            TransferCheckedWithFee::deserialize(&mut &rest[..])
+                .ok()
+                .map(|transfer| (0, 2, transfer.amount, Some(transfer.fee)))
+        }
+        _ => None,
+    }?;
+    let account_at = |idx: usize| {
+        inst.accounts
+            .get(idx)
+            .and_then(|&account_idx| accounts.get(account_idx as usize))
+            .map(String::as_str)
+    };
+
+    Some(TokenTransferInstruction {
+        source: account_at(source_idx)?,
+        destination: account_at(destination_idx)?,
+        amount,
+        fee,
+        is_token_2022,
+    })
 }
 
-pub fn get_token_transfer(
-    address: &String,
-    input_inner_idx: u32,
-    inner_instructions: &Vec<UiInnerInstructions>,
-    accounts: &Vec<String>,
-    account_name_to_check: String,
-    dapp_address: String,
-    pre_balances: Vec<u64>,
-    post_balances: Vec<u64>,
-) -> f64 {
-    if dapp_address.eq("6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P") {
-        return get_system_program_transfer(
-            address,
-            input_inner_idx,
-            inner_instructions,
-            accounts,
-            account_name_to_check,
-            pre_balances,
-            post_balances,
-        );
-    }
-
-    let mut result = 0.0;
-    let mut result_assigned = false;
-
-    inner_instructions.iter().for_each(|inner_instruction| {
-        inner_instruction
-            .instructions
-            .iter()
-            .enumerate()
-            .for_each(|(inner_idx, inner_inst)| {
-                let inner_inst = match inner_inst {
-                    UiInstruction::Parsed(_) => return,
-                    UiInstruction::Compiled(compiled) => compiled,
-                };
-                let program_id_index = inner_inst.program_id_index as usize;
-                if program_id_index >= accounts.len() {
-                    return;
+/// Net effect that SPL Token / Token-2022 `Transfer`, `TransferChecked` and
+/// `TransferCheckedWithFee` instructions among `instructions` had on
+/// `token_account`. `None` if none of them touched the account.
+///
+/// A destination is credited the amount less the fee, which Token-2022 keeps
+/// withheld in the destination account; the source is debited the full
+/// amount. Plain transfers of fee-bearing mints don't carry the fee in their
+/// data, so what they credit is also tracked in `unstated_fee_credit` for the
+/// caller to net against the account's balance diff.
+pub fn get_token_transfer<'a>(
+    token_account: &str,
+    instructions: impl IntoIterator<Item = &'a InnerInstruction>,
+    accounts: &[String],
+) -> Option<TokenTransfer> {
+    let mut result: Option<TokenTransfer> = None;
+
+    for transfer in instructions
+        .into_iter()
+        .filter_map(|inst| parse_token_transfer(inst, accounts))
+    {
+        if transfer.source == token_account {
+            let total = result.get_or_insert_with(TokenTransfer::default);
+            total.amount -= transfer.amount as i128;
+        }
+        if transfer.destination == token_account {
+            let total = result.get_or_insert_with(TokenTransfer::default);
+            match transfer.fee {
+                Some(fee) => {
+                    total.amount += transfer.amount as i128 - fee as i128;
+                    total.fee += fee;
                 }
-                let inner_program = &accounts[program_id_index];
-                if inner_program
-                    .as_str()
-                    .eq("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")
-                {
-                    // println!("Inner Program: {:?}", inner_program);
-                    // println!("Data: {:?}", inner_inst.data.clone().into_bytes());
-                    let data = bs58::decode(inner_inst.data.clone())
-                        .into_vec()
-                        .expect("Error decoding data");
-                    let (discriminator_bytes, rest) = data.split_at(1);
-                    let discriminator: u8 = u8::from(discriminator_bytes[0]);
-
-                    match discriminator {
-                        3 => {
-                            let input_accounts =
-                                prepare_input_accounts(&inner_inst.accounts, accounts);
-
-                            let source = input_accounts.get(0).unwrap().to_string();
-                            let destination = input_accounts.get(1).unwrap().to_string();
-
-                            let condition = if input_inner_idx > 0 {
-                                inner_idx as u32 > input_inner_idx
-                            } else {
-                                true
-                            };
-
-                            if condition && address.eq(&source) {
-                                let data = Transfer::deserialize(&mut rest.clone()).unwrap();
-                                if !result_assigned {
-                                    result = -1.0 * data.amount as f64;
-                                    result_assigned = true;
-                                }
-                            }
-
-                            if condition && address.eq(&destination) {
-                                let data = Transfer::deserialize(&mut rest.clone()).unwrap();
-                                if !result_assigned {
-                                    result = data.amount as f64;
-                                    result_assigned = true;
-                                }
-                            }
-                        }
-                        12 => {
-                            let input_accounts =
-                                prepare_input_accounts(&inner_inst.accounts, accounts);
-
-                            let source = input_accounts.get(0).unwrap().to_string();
-                            let destination = input_accounts.get(2).unwrap().to_string();
-
-                            let condition = if input_inner_idx > 0 {
-                                inner_idx as u32 > input_inner_idx
-                            } else {
-                                true
-                            };
-
-                            if condition && address.eq(&source) {
-                                let data = Transfer::deserialize(&mut rest.clone()).unwrap();
-                                if !result_assigned {
-                                    result = -1.0 * data.amount as f64;
-                                    result_assigned = true;
-                                }
-                            }
-
-                            if condition && address.eq(&destination) {
-                                let data = Transfer::deserialize(&mut rest.clone()).unwrap();
-                                if !result_assigned {
-                                    result = data.amount as f64;
-                                    result_assigned = true;
-                                }
-                            }
-                        }
-                        _ => {}
+                None => {
+                    total.amount += transfer.amount as i128;
+                    if transfer.is_token_2022 {
+                        total.unstated_fee_credit += transfer.amount as i128;
                     }
                 }
-            })
-    });
-
-    if !result_assigned {
-        let _result = get_token_22_transfer(
-            address,
-            input_inner_idx,
-            inner_instructions,
-            accounts,
-            account_name_to_check,
-        );
-        if _result.is_some() {
-            result = _result.unwrap();
+            }
         }
     }
 
     result
 }
 
-pub fn get_token_22_transfer(
-    address: &String,
-    input_inner_idx: u32,
-    inner_instructions: &Vec<UiInnerInstructions>,
-    accounts: &Vec<String>,
-    account_name_to_check: String,
-) -> Option<f64> {
-    let mut result = None;
-    let mut result_assigned = false;
-
-    inner_instructions.iter().for_each(|inner_instruction| {
-        inner_instruction
-            .instructions
-            .iter()
-            .enumerate()
-            .for_each(|(inner_idx, inner_inst)| {
-                let inner_inst = match inner_inst {
-                    UiInstruction::Parsed(_) => return,
-                    UiInstruction::Compiled(compiled) => compiled,
-                };
-                let inner_program = &accounts[inner_inst.program_id_index as usize];
-
-                if inner_program
-                    .as_str()
-                    .eq("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb")
-                {
-                    let data = bs58::decode(inner_inst.data.clone())
-                        .into_vec()
-                        .expect("Error decoding data");
-                    let (discriminator_bytes, rest) = data.split_at(1);
-                    let discriminator: u8 = u8::from(discriminator_bytes[0]);
-
-                    match discriminator {
-                        3 => {
-                            let input_accounts =
-                                prepare_input_accounts(&inner_inst.accounts, accounts);
-
-                            let source = input_accounts.get(0).unwrap().to_string();
-                            let destination = input_accounts.get(1).unwrap().to_string();
-
-                            let condition = if input_inner_idx > 0 {
-                                inner_idx as u32 > input_inner_idx
-                            } else {
-                                true
-                            };
-
-                            if condition && address.eq(&source) {
-                                let data = Transfer::deserialize(&mut rest.clone()).unwrap();
-                                if !result_assigned {
-                                    result = Some(-1.0 * data.amount as f64);
-                                    result_assigned = true;
-                                }
-                            }
-
-                            if condition && address.eq(&destination) {
-                                let data = Transfer::deserialize(&mut rest.clone()).unwrap();
-                                if !result_assigned {
-                                    result = Some(data.amount as f64);
-                                    result_assigned = true;
-                                }
-                            }
-                        }
-                        12 => {
-                            let input_accounts =
-                                prepare_input_accounts(&inner_inst.accounts, accounts);
-
-                            let source = input_accounts.get(0).unwrap().to_string();
-                            let destination = input_accounts.get(2).unwrap().to_string();
-
-                            let condition = if input_inner_idx > 0 {
-                                inner_idx as u32 > input_inner_idx
-                            } else {
-                                true
-                            };
-
-                            if condition && address.eq(&source) {
-                                let data = Transfer::deserialize(&mut rest.clone()).unwrap();
-                                if !result_assigned {
-                                    result = Some(-1.0 * data.amount as f64);
-                                    result_assigned = true;
-                                }
-                            }
-
-                            if condition && address.eq(&destination) {
-                                let data = Transfer::deserialize(&mut rest.clone()).unwrap();
-                                if !result_assigned {
-                                    result = Some(data.amount as f64);
-                                    result_assigned = true;
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-            })
-    });
-
-    result
+/// Token accounts on the other side of the token transfers among
+/// `instructions` that moved `token_account`, in instruction order.
+pub fn get_transfer_counterparties<'a, 'b>(
+    token_account: &str,
+    instructions: impl IntoIterator<Item = &'a InnerInstruction>,
+    accounts: &'b [String],
+) -> Vec<&'b str> {
+    instructions
+        .into_iter()
+        .filter_map(|inst| parse_token_transfer(inst, accounts))
+        .filter_map(|transfer| {
+            if transfer.source == token_account {
+                Some(transfer.destination)
+            } else if transfer.destination == token_account {
+                Some(transfer.source)
+            } else {
+                None
+            }
+        })
+        .collect()
 }
 
 pub fn prepare_input_accounts(account_indices: &Vec<u8>, accounts: &Vec<String>) -> Vec<String> {
@@ -478,96 +517,22 @@ pub fn prepare_input_accounts(account_indices: &Vec<u8>, accounts: &Vec<String>)
     instruction_accounts
 }
 
-fn get_system_program_transfer(
-    address: &String,
-    input_inner_idx: u32,
-    inner_instructions: &Vec<UiInnerInstructions>,
-    accounts: &Vec<String>,
-    account_name_to_check: String,
-    pre_balances: Vec<u64>,
-    post_balances: Vec<u64>,
-) -> f64 {
-    let mut result = 0.0;
-    let mut result_assigned = false;
-
-    inner_instructions.iter().for_each(|inner_instruction| {
-        inner_instruction
-            .instructions
-            .iter()
-            .enumerate()
-            .for_each(|(inner_idx, inner_inst)| {
-                let inner_inst = match inner_inst {
-                    UiInstruction::Parsed(_) => return,
-                    UiInstruction::Compiled(compiled) => compiled,
-                };
-                let inner_program = &accounts[inner_inst.program_id_index as usize];
-
-                if inner_program
-                    .as_str()
-                    .eq("11111111111111111111111111111111")
-                {
-                    // decode hex
-                    let data = bs58::decode(inner_inst.data.clone())
-                        .into_vec()
-                        .expect("Error decoding data");
-                    let (discriminator_bytes, rest) = data.split_at(4);
-
-                    let disc_bytes_arr: [u8; 4] = discriminator_bytes.to_vec().try_into().unwrap();
-                    let discriminator: u32 = u32::from_le_bytes(disc_bytes_arr);
-
-                    match discriminator {
-                        2 => {
-                            let input_accounts =
-                                prepare_input_accounts(&inner_inst.accounts, accounts);
-
-                            let source = input_accounts.get(0).unwrap().to_string();
-                            let destination = input_accounts.get(1).unwrap().to_string();
-
-                            let condition = if input_inner_idx > 0 {
-                                inner_idx as u32 > input_inner_idx
-                            } else {
-                                true
-                            };
-
-                            if condition && address.eq(&source) {
-                                let data = Transfer::deserialize(&mut rest.clone()).unwrap();
-                                if !result_assigned {
-                                    result = -1.0 * data.amount as f64;
-                                    result /= 10f64.powi(9);
-                                    result_assigned = true;
-                                }
-                            }
-
-                            if condition && address.eq(&destination) {
-                                let data = Transfer::deserialize(&mut rest.clone()).unwrap();
-                                if !result_assigned {
-                                    result = 1.0 * data.amount as f64;
-                                    result /= 10f64.powi(9);
-                                    result_assigned = true;
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-            })
-    });
-
-    if !result_assigned {
-        let index = accounts.iter().position(|r| r == address).unwrap();
-        let _result = post_balances[index] as f64 - pre_balances[index] as f64;
-        result = 1.0 * _result as f64;
-        result /= 10f64.powi(9);
-    }
-
-    result
-}
-
-pub async fn save_trades_to_avro(trades: &Vec<TradeData>, date_str: &str, slot: u64) -> Result<()> {
-    if trades.is_empty() {
-        return Err(anyhow::anyhow!("No trades to save"));
-    }
-    let folder = format!("{}{}", OUTPUT_PATH.as_str(), date_str);
+/// Writes a block's rows of one dataset to
+/// `{OUTPUT_PATH}{date}/{dataset}/{slot}.avro`, or straight under the date
+/// folder for an empty `dataset`, replacing any earlier file. Rows are
+/// serialized with serde and matched to `schema` by field name.
+pub async fn save_to_avro<T: Serialize>(
+    rows: &[T],
+    schema: &Schema,
+    dataset: &str,
+    date_str: &str,
+    slot: u64,
+) -> Result<()> {
+    let folder = if dataset.is_empty() {
+        format!("{}{}", OUTPUT_PATH.as_str(), date_str)
+    } else {
+        format!("{}{}/{}", OUTPUT_PATH.as_str(), date_str, dataset)
+    };
     if !Path::new(&folder).exists() {
         create_dir_all(&folder)?;
     }
@@ -580,43 +545,33 @@ pub async fn save_trades_to_avro(trades: &Vec<TradeData>, date_str: &str, slot:
         .write(true)
         .open(&file_path)?;
 
-    let mut writer = Writer::new(&AVRO_SCHEMA, file);
-
-    for trade in trades.clone() {
-        let mut record = Record::new(&AVRO_SCHEMA).expect("Failed to create Avro record");
-        record.put("block_date", trade.block_date.clone());
-        record.put("block_time", trade.block_time);
-        // Convert u64 to i64
-        record.put("block_slot", trade.block_slot as i64);
-        record.put("signature", trade.signature.clone());
-        record.put("tx_id", trade.tx_id.clone());
-        record.put("signer", trade.signer.clone());
-        record.put("pool_address", trade.pool_address.clone());
-        record.put("base_mint", trade.base_mint.clone());
-        record.put("quote_mint", trade.quote_mint.clone());
-        record.put("base_vault", trade.base_vault.clone());
-        record.put("quote_vault", trade.quote_vault.clone());
-        record.put("base_amount", trade.base_amount);
-        record.put("quote_amount", trade.quote_amount);
-        record.put("is_inner_instruction", trade.is_inner_instruction);
-        record.put("instruction_index", trade.instruction_index as i32);
-        record.put("instruction_type", trade.instruction_type.clone());
-        record.put(
-            "inner_instruction_index",
-            trade.inner_instruction_index as i32,
-        );
-        record.put("outer_program", trade.outer_program.clone());
-        record.put("inner_program", trade.inner_program.clone());
-        record.put("txn_fee_lamports", trade.txn_fee_lamports as i64);
-        record.put(
-            "signer_lamports_change",
-            trade.signer_lamports_change as i64,
-        );
+    let mut writer = Writer::new(schema, file);
 
-        writer.append(record)?;
+    for row in rows {
+        writer.append(to_value(row)?.resolve(schema)?)?;
     }
     writer.flush()?;
-    
+
+    Ok(())
+}
+
+/// Per-reason counts of the instructions a block's decode pass skipped,
+/// written to `{OUTPUT_PATH}{date}/skipped/{slot}.json`.
+pub async fn save_skipped_to_json(skipped: &SkippedCounts, date_str: &str, slot: u64) -> Result<()> {
+    let folder = format!("{}{}/skipped", OUTPUT_PATH.as_str(), date_str);
+    if !Path::new(&folder).exists() {
+        create_dir_all(&folder)?;
+    }
+
+    let file_path = format!("{}/{}.json", folder, slot);
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&file_path)?;
+    serde_json::to_writer(file, skipped)?;
+
     Ok(())
 }
 
@@ -649,3 +604,151 @@ pub async fn save_trades_to_csv(trades: &Vec<TradeData>, file_path: &str) -> Res
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accounts() -> Vec<String> {
+        [
+            TOKEN_PROGRAM_ID,
+            TOKEN_2022_PROGRAM_ID,
+            "source",
+            "destination",
+            "authority",
+            "mint",
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    fn instruction(program_id_index: u32, accounts: Vec<u8>, data: Vec<u8>) -> InnerInstruction {
+        InnerInstruction {
+            program_id_index,
+            accounts,
+            data,
+            stack_height: Some(2),
+        }
+    }
+
+    /// `Transfer` (tag 3): source, destination, authority.
+    fn transfer(program_id_index: u32, amount: u64) -> InnerInstruction {
+        let mut data = vec![3];
+        data.extend(amount.to_le_bytes());
+        instruction(program_id_index, vec![2, 3, 4], data)
+    }
+
+    /// `TransferChecked` (tag 12): source, mint, destination, authority.
+    fn transfer_checked(program_id_index: u32, amount: u64) -> InnerInstruction {
+        let mut data = vec![12];
+        data.extend(amount.to_le_bytes());
+        data.push(6);
+        instruction(program_id_index, vec![2, 5, 3, 4], data)
+    }
+
+    /// `TransferCheckedWithFee` (tags 26, 1), same accounts as `TransferChecked`.
+    fn transfer_checked_with_fee(amount: u64, fee: u64) -> InnerInstruction {
+        let mut data = vec![26, 1];
+        data.extend(amount.to_le_bytes());
+        data.push(6);
+        data.extend(fee.to_le_bytes());
+        instruction(1, vec![2, 5, 3, 4], data)
+    }
+
+    #[test]
+    fn transfer_moves_both_sides() {
+        let instructions = [transfer(0, 500)];
+        let source = get_token_transfer("source", &instructions, &accounts()).unwrap();
+        let destination = get_token_transfer("destination", &instructions, &accounts()).unwrap();
+        assert_eq!(source.amount, -500);
+        assert_eq!(destination.amount, 500);
+        assert_eq!(destination.fee, 0);
+        assert_eq!(destination.unstated_fee_credit, 0);
+    }
+
+    #[test]
+    fn transfer_checked_reads_destination_past_the_mint() {
+        let instructions = [transfer_checked(0, 700)];
+        let destination = get_token_transfer("destination", &instructions, &accounts()).unwrap();
+        assert_eq!(destination.amount, 700);
+        assert!(get_token_transfer("mint", &instructions, &accounts()).is_none());
+    }
+
+    #[test]
+    fn token_2022_transfer_checked_credits_unstated_fee() {
+        let instructions = [transfer_checked(1, 700)];
+        let source = get_token_transfer("source", &instructions, &accounts()).unwrap();
+        let destination = get_token_transfer("destination", &instructions, &accounts()).unwrap();
+        assert_eq!(source.unstated_fee_credit, 0);
+        assert_eq!(destination.amount, 700);
+        assert_eq!(destination.unstated_fee_credit, 700);
+    }
+
+    #[test]
+    fn transfer_checked_with_fee_nets_fee_on_destination_only() {
+        let instructions = [transfer_checked_with_fee(1_000, 25)];
+        let source = get_token_transfer("source", &instructions, &accounts()).unwrap();
+        let destination = get_token_transfer("destination", &instructions, &accounts()).unwrap();
+        assert_eq!(source.amount, -1_000);
+        assert_eq!(source.fee, 0);
+        assert_eq!(destination.amount, 975);
+        assert_eq!(destination.fee, 25);
+    }
+
+    #[test]
+    fn transfers_of_other_programs_are_ignored() {
+        let instructions = [transfer(4, 500)];
+        assert!(get_token_transfer("source", &instructions, &accounts()).is_none());
+    }
+
+    #[test]
+    fn counterparties_are_the_other_side_of_each_transfer() {
+        let instructions = [transfer(0, 500), transfer_checked(0, 700)];
+        assert_eq!(
+            get_transfer_counterparties("destination", &instructions, &accounts()),
+            vec!["source", "source"]
+        );
+        assert!(get_transfer_counterparties("authority", &instructions, &accounts()).is_empty());
+    }
+
+    fn trade() -> TradeData {
+        TradeData {
+            signature: String::from("signature"),
+            base_amount_raw: -1_000,
+            quote_amount_raw: 2_000,
+            trader_accounts: vec![String::from("source"), String::from("authority")],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn trades_append_to_csv_with_one_header() {
+        let path = std::env::temp_dir().join(format!("trades-{}.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let trades = vec![trade(), TradeData::default()];
+
+        save_trades_to_csv(&trades, path).await.unwrap();
+        save_trades_to_csv(&trades, path).await.unwrap();
+
+        let read: Vec<TradeData> = csv::Reader::from_path(path)
+            .unwrap()
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(read, [trades.clone(), trades].concat());
+    }
+
+    #[test]
+    fn trades_resolve_against_the_avro_schema() {
+        let value = to_value(trade()).unwrap().resolve(&AVRO_SCHEMA).unwrap();
+        let avro_rs::types::Value::Record(fields) = value else {
+            panic!("trade is not a record");
+        };
+        assert!(fields.contains(&(
+            String::from("trader_accounts"),
+            avro_rs::types::Value::String(String::from("source,authority"))
+        )));
+    }
+}
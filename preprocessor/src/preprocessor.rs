@@ -22,6 +22,7 @@ const PUMP_FUN_SUPPLY: f64 = 1_000_000_000.0; // 1 billion
 
 const SOL_ADDRESS: &str = &"So11111111111111111111111111111111111111112";
 const USDC_ADDRESS: &str = &"EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+const USDT_ADDRESS: &str = &"Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB";
 
 // lazy_static!(
 //     // SOLSCAN API KEY FROM ENV
@@ -430,26 +431,26 @@ impl Preprocessor {
         }
 
         for trade in trades {
-            let traded_token: String;
-            let sol_amount;
-            let token_amount;
-            if trade.base_mint != "So11111111111111111111111111111111111111112" {
-                traded_token = trade.base_mint.clone();
-                sol_amount = trade.quote_amount;
-                token_amount = trade.base_amount;
-            } else {
-                traded_token = trade.quote_mint.clone();
-                sol_amount = trade.base_amount;
-                token_amount = trade.quote_amount;
-            }
-
-            let token_sol_price = sol_amount/token_amount;
+            // the traded token is whichever side isn't the quote mint
+            let (traded_token, token_amount, quote_mint, quote_amount) = match trade.side.as_str() {
+                "Buy" => (&trade.output_mint, trade.amount_out, &trade.input_mint, trade.amount_in),
+                "Sell" => (&trade.input_mint, trade.amount_in, &trade.output_mint, trade.amount_out),
+                _ => continue,
+            };
+            let side = if trade.side == "Buy" { Buy } else { Sell };
 
-            // if !traded_token.ends_with("pump") {
-            //     continue;
-            // }
+            let derived_price = quote_amount / token_amount;
 
             let sol_price = Some(self.get_sol_price(trade.block_time.try_into().unwrap()).await).expect("Failed to get SOL price");
+            let (quote_asset, quote_usd_price) = match quote_mint.as_str() {
+                SOL_ADDRESS => ("SOL", sol_price),
+                USDC_ADDRESS => ("USDC", 1.0),
+                USDT_ADDRESS => ("USDT", 1.0),
+                // no price feed for other quotes, leave USD values at 0
+                _ => (quote_mint.as_str(), 0.0),
+            };
+            let usd_price = derived_price * quote_usd_price;
+
             // let meta = self
             //     .get_token_meta(&traded_token)
             //     .await?;
@@ -462,16 +463,28 @@ impl Preprocessor {
             // };
 
             let mcap = if traded_token.ends_with("pump") {
-                (PUMP_FUN_SUPPLY * token_sol_price * sol_price).abs()
+                PUMP_FUN_SUPPLY * usd_price
             } else {
                 0.0
             };
 
-            let exchange = match trade.outer_program.as_str() {
+            // swaps routed through an aggregator carry the venue in inner_program
+            let venue = if trade.inner_program.is_empty() {
+                trade.outer_program.as_str()
+            } else {
+                trade.inner_program.as_str()
+            };
+
+            let exchange = match venue {
                 "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8" => "RAYDIUM",
+                "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK" => "RAYDIUM",
+                "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C" => "RAYDIUM",
                 "Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB" => "METEORA",
                 "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo" => "METEORA",
                 "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc" => "ORCA",
+                "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P" => "PUMPFUN",
+                "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA" => "PUMPSWAP",
+                "MoonCVVNZFSYkqNXP6bxHLPL6QQJiMagDL3qcqUQTrG" => "MOONSHOT",
                 _ => "UNKNOWN",
             };
 
@@ -482,13 +495,13 @@ impl Preprocessor {
                 signature: trade.signature.clone(),
                 exchange: exchange.to_string(),
                 token: traded_token.clone(),
-                side: if sol_amount > 0.0 { Buy } else { Sell },
-                token_amount: token_amount.abs(),
-                sol_amount: sol_amount.abs(),
-                sol_usd_price: sol_price,
-                sol_price: token_sol_price.abs(),
-                usd_price: (token_sol_price * sol_price).abs(),
-                volume: (sol_amount.abs() * sol_price).abs(),
+                side,
+                token_amount,
+                quote_asset: quote_asset.to_string(),
+                quote_amount,
+                derived_price,
+                usd_price,
+                volume: quote_amount * quote_usd_price,
                 market_cap: mcap,
             };
 
@@ -514,9 +527,9 @@ impl Preprocessor {
                 let token_col: Vec<String> = trades.iter().map(|t| t.token.clone()).collect();
                 let side_col: Vec<String> = trades.iter().map(|t| t.side.to_string()).collect();
                 let token_amount: Vec<f64> = trades.iter().map(|t| t.token_amount).collect();
-                let sol_amount: Vec<f64> = trades.iter().map(|t| t.sol_amount).collect();
-                let sol_usd_price: Vec<f64> = trades.iter().map(|t| t.sol_usd_price).collect();
-                let sol_price: Vec<f64> = trades.iter().map(|t| t.sol_price).collect();
+                let quote_asset: Vec<String> = trades.iter().map(|t| t.quote_asset.clone()).collect();
+                let quote_amount: Vec<f64> = trades.iter().map(|t| t.quote_amount).collect();
+                let derived_price: Vec<f64> = trades.iter().map(|t| t.derived_price).collect();
                 let usd_price: Vec<f64> = trades.iter().map(|t| t.usd_price).collect();
                 let volume: Vec<f64> = trades.iter().map(|t| t.volume).collect();
                 let market_cap: Vec<f64> = trades.iter().map(|t| t.market_cap).collect();
@@ -531,9 +544,9 @@ impl Preprocessor {
                     "token" => token_col,
                     "side" => side_col,
                     "token_amount" => token_amount,
-                    "sol_amount" => sol_amount,
-                    "sol_usd_price" => sol_usd_price,
-                    "sol_price" => sol_price,
+                    "quote_asset" => quote_asset,
+                    "quote_amount" => quote_amount,
+                    "derived_price" => derived_price,
                     "usd_price" => usd_price,
                     "volume" => volume,
                     "market_cap" => market_cap